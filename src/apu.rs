@@ -0,0 +1,93 @@
+const POWER: u8 = 1 << 7;
+
+/// Bits of NR10-NR52 (0xFF10-0xFF26) that always read as 1, because they
+/// are unused or write-only. Unmapped addresses read as 0xFF.
+const READ_MASKS: [u8; 0x17] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+];
+
+/// The sound registers and wave RAM. No audio is generated yet, so the
+/// channel status bits in NR52 only change when the APU is powered off.
+pub struct APU {
+    registers: [u8; 0x17],
+    wave_ram: [u8; 0x10],
+    powered: bool,
+    channels: u8,
+    /// Step of the 512 Hz frame sequencer that clocks length, sweep and
+    /// envelope.
+    frame_step: u8,
+    /// The divider bit that clocks the frame sequencer, as of the last tick.
+    sequencer_input: bool,
+}
+
+impl APU {
+    pub fn new() -> Self {
+        Self {
+            registers: [0; 0x17],
+            wave_ram: [0; 0x10],
+            powered: true,
+            channels: 0,
+            frame_step: 0,
+            sequencer_input: false,
+        }
+    }
+
+    /// Advances the frame sequencer on the falling edge of bit 12 of the
+    /// timer's divider (bit 13 in double speed mode), so writes to DIV
+    /// can clock it early.
+    pub fn tick(&mut self, divider: u16, double_speed: bool) {
+        let bit = if double_speed { 13 } else { 12 };
+        let input = divider & (1 << bit) != 0;
+        if self.sequencer_input && !input && self.powered {
+            self.frame_step = (self.frame_step + 1) & 7;
+        }
+        self.sequencer_input = input;
+    }
+
+    /// Sets the channel status bits of NR52, such as for the boot sound
+    /// still playing when the cartridge takes over.
+    pub fn set_channels(&mut self, channels: u8) {
+        self.channels = channels & 0x0F;
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF26 => {
+                let power = if self.powered { POWER } else { 0 };
+                READ_MASKS[0x16] | power | self.channels
+            }
+            0xFF10..=0xFF25 => {
+                let index = (address - 0xFF10) as usize;
+                self.registers[index] | READ_MASKS[index]
+            }
+            0xFF30..=0xFF3F => self.wave_ram[(address & 0xF) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF26 => {
+                let powered = value & POWER != 0;
+                if powered && !self.powered {
+                    self.frame_step = 0;
+                }
+                self.powered = powered;
+                if !self.powered {
+                    self.registers = [0; 0x17];
+                    self.channels = 0;
+                }
+            }
+            // While powered off all registers except NR52 are read-only
+            0xFF10..=0xFF25 if self.powered => {
+                self.registers[(address - 0xFF10) as usize] = value;
+            }
+            0xFF30..=0xFF3F => self.wave_ram[(address & 0xF) as usize] = value,
+            _ => (),
+        }
+    }
+}
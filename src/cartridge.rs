@@ -0,0 +1,358 @@
+use std::error;
+use std::fmt;
+
+use crate::mbc::{
+    Camera, HuC1, HuC3, ImageSource, Mbc, Mbc1, Mbc2, Mbc3, Mbc5, Mbc7, RomOnly, MBC2_RAM_SIZE,
+    MBC7_EEPROM_SIZE, ROM_BANK_SIZE,
+};
+
+const HEADER_END: usize = 0x150;
+
+/// The logo the boot ROM compares against 0x0104-0x0133. It refuses to
+/// start any cartridge where they differ.
+const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    /// The image is too small to even contain a header.
+    TooSmall(usize),
+    InvalidLogo,
+    HeaderChecksum {
+        expected: u8,
+        actual: u8,
+    },
+    InvalidRomSize(u8),
+    InvalidRamSize(u8),
+    /// The image is smaller than the ROM size declared in the header.
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    UnsupportedType(u8),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::TooSmall(len) => {
+                f.write_fmt(format_args!("image too small for a header: {} bytes", len))
+            }
+            CartridgeError::InvalidLogo => f.write_str("invalid Nintendo logo"),
+            CartridgeError::HeaderChecksum { expected, actual } => f.write_fmt(format_args!(
+                "header checksum mismatch: expected {:02X}, got {:02X}",
+                expected, actual
+            )),
+            CartridgeError::InvalidRomSize(code) => {
+                f.write_fmt(format_args!("invalid ROM size code: {:02X}", code))
+            }
+            CartridgeError::InvalidRamSize(code) => {
+                f.write_fmt(format_args!("invalid RAM size code: {:02X}", code))
+            }
+            CartridgeError::Truncated { expected, actual } => f.write_fmt(format_args!(
+                "image truncated: expected {} bytes, got {}",
+                expected, actual
+            )),
+            CartridgeError::UnsupportedType(code) => {
+                f.write_fmt(format_args!("unsupported cartridge type: {:02X}", code))
+            }
+        }
+    }
+}
+
+impl error::Error for CartridgeError {}
+
+/// The cartridge header at 0x0100-0x014F.
+#[derive(Debug)]
+pub struct Header {
+    pub title: String,
+    pub manufacturer: String,
+    pub cgb_flag: u8,
+    pub sgb_flag: u8,
+    pub cartridge_type: u8,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    pub licensee: String,
+    /// The licensee code at 0x014B, 0x33 meaning the new one is used.
+    pub old_licensee: u8,
+    pub version: u8,
+    pub header_checksum: u8,
+    /// Sum of the 16 title bytes, which the CGB boot ROM uses to pick a
+    /// palette for DMG games.
+    pub title_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, CartridgeError> {
+        if data.len() < HEADER_END {
+            return Err(CartridgeError::TooSmall(data.len()));
+        }
+        if data[0x104..0x134] != NINTENDO_LOGO {
+            return Err(CartridgeError::InvalidLogo);
+        }
+        let expected = data[0x14D];
+        let actual = header_checksum(data);
+        if expected != actual {
+            return Err(CartridgeError::HeaderChecksum { expected, actual });
+        }
+
+        let cgb_flag = data[0x143];
+        // On CGB-era cartridges the end of the title area holds the
+        // manufacturer code and the CGB flag
+        let (title, manufacturer) = if cgb_flag & 0x80 != 0 {
+            (ascii(&data[0x134..0x13F]), ascii(&data[0x13F..0x143]))
+        } else {
+            (ascii(&data[0x134..0x144]), String::new())
+        };
+        let licensee = match data[0x14B] {
+            0x33 => ascii(&data[0x144..0x146]),
+            code => format!("{:02X}", code),
+        };
+        let rom_size = match data[0x148] {
+            code @ 0x00..=0x08 => (2 * ROM_BANK_SIZE) << code,
+            code => return Err(CartridgeError::InvalidRomSize(code)),
+        };
+        let ram_size = match data[0x149] {
+            0x00 => 0,
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            code => return Err(CartridgeError::InvalidRamSize(code)),
+        };
+
+        Ok(Self {
+            title,
+            manufacturer,
+            cgb_flag,
+            sgb_flag: data[0x146],
+            cartridge_type: data[0x147],
+            rom_size,
+            ram_size,
+            licensee,
+            old_licensee: data[0x14B],
+            version: data[0x14C],
+            header_checksum: expected,
+            title_checksum: data[0x134..0x144]
+                .iter()
+                .fold(0u8, |sum, byte| sum.wrapping_add(*byte)),
+            global_checksum: u16::from_be_bytes([data[0x14E], data[0x14F]]),
+        })
+    }
+
+    /// Whether the game uses CGB features. Others run in DMG compatibility
+    /// mode on a CGB.
+    pub fn supports_cgb(&self) -> bool {
+        self.cgb_flag & 0x80 != 0
+    }
+
+    /// The SGB only accepts command packets from games that declare support
+    /// and use the new licensee code.
+    pub fn supports_sgb(&self) -> bool {
+        self.sgb_flag == 0x03 && self.old_licensee == 0x33
+    }
+
+    /// Nintendo, either through the old or the new licensee code.
+    pub fn is_nintendo(&self) -> bool {
+        self.licensee == "01"
+    }
+}
+
+fn header_checksum(data: &[u8]) -> u8 {
+    data[0x134..0x14D]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+/// Sum of every byte in the image except the checksum itself.
+fn global_checksum(data: &[u8]) -> u16 {
+    data.iter()
+        .enumerate()
+        .filter(|(address, _)| *address != 0x14E && *address != 0x14F)
+        .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+}
+
+/// MBC1M multicarts are 1 MiB images made of up to four 256 KiB games,
+/// each starting with its own header. Regular MBC1 games of that size don't
+/// repeat the Nintendo logo at the start of bank 0x10.
+fn is_mbc1_multicart(rom: &[u8]) -> bool {
+    let logo = 0x10 * ROM_BANK_SIZE + 0x104;
+    rom.len() == 0x100000 && rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|byte| **byte != 0)
+        .map(|byte| *byte as char)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+pub struct Cartridge {
+    pub header: Header,
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mbc: Box<dyn Mbc>,
+    /// RAM or other saved state was written since the last `take_dirty`.
+    dirty: bool,
+}
+
+impl Cartridge {
+    pub fn new(rom: Vec<u8>) -> Result<Self, CartridgeError> {
+        let header = Header::parse(&rom)?;
+        if rom.len() < header.rom_size {
+            return Err(CartridgeError::Truncated {
+                expected: header.rom_size,
+                actual: rom.len(),
+            });
+        }
+        let mbc: Box<dyn Mbc> = match header.cartridge_type {
+            // ROM only, optionally with RAM and battery
+            0x00 | 0x08 | 0x09 => Box::new(RomOnly),
+            0x01..=0x03 => {
+                let multicart = is_mbc1_multicart(&rom);
+                if multicart {
+                    info!("detected MBC1M multicart");
+                }
+                Box::new(Mbc1::new(multicart))
+            }
+            0x05 | 0x06 => Box::new(Mbc2::new()),
+            0x0F | 0x10 => Box::new(Mbc3::new(true)),
+            0x11..=0x13 => Box::new(Mbc3::new(false)),
+            0x19..=0x1B => Box::new(Mbc5::new(false)),
+            0x1C..=0x1E => Box::new(Mbc5::new(true)),
+            0x22 => Box::new(Mbc7::new()),
+            0xFC => Box::new(Camera::new()),
+            0xFE => Box::new(HuC3::new()),
+            0xFF => Box::new(HuC1::new()),
+            code => return Err(CartridgeError::UnsupportedType(code)),
+        };
+        // Only the header checksum is verified by the boot ROM, so plenty of
+        // working images get the global one wrong
+        let checksum = global_checksum(&rom[..header.rom_size]);
+        if checksum != header.global_checksum {
+            warn!(
+                "global checksum mismatch: expected {:04X}, got {:04X}",
+                header.global_checksum, checksum
+            );
+        }
+
+        debug!(
+            "cartridge header: title {:?}, manufacturer {:?}, licensee {}, version {}, CGB {:02X}, SGB {:02X}",
+            header.title,
+            header.manufacturer,
+            header.licensee,
+            header.version,
+            header.cgb_flag,
+            header.sgb_flag
+        );
+
+        // MBC2 has its RAM built in and MBC7 saves to an EEPROM, so the
+        // header declares no RAM for either. An erased EEPROM reads all 1s.
+        let ram = match header.cartridge_type {
+            0x05 | 0x06 => vec![0; MBC2_RAM_SIZE],
+            0x22 => vec![0xFF; MBC7_EEPROM_SIZE],
+            _ => vec![0; header.ram_size],
+        };
+        Ok(Self {
+            header,
+            rom,
+            ram,
+            mbc,
+            dirty: false,
+        })
+    }
+
+    pub fn read_rom(&self, address: u16) -> u8 {
+        self.mbc.read_rom(&self.rom, address)
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        self.mbc.write_rom(address, value);
+    }
+
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.mbc.read_ram(&self.ram, address)
+    }
+
+    pub fn write_ram(&mut self, address: u16, value: u8) {
+        if self.mbc.write_ram(&mut self.ram, address, value) {
+            self.dirty = true;
+        }
+    }
+
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self.header.cartridge_type,
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFC..=0xFF
+        )
+    }
+
+    /// Whether the bank controller has a clock whose state is saved.
+    pub fn has_clock(&self) -> bool {
+        !self.mbc.save_state().is_empty()
+    }
+
+    pub fn ram_enabled(&self) -> bool {
+        self.mbc.ram_enabled()
+    }
+
+    /// Returns whether the save data changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mbc.set_tilt(x, y);
+    }
+
+    pub fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.mbc.set_image_source(source);
+    }
+
+    pub fn tick(&mut self, cycles: usize) {
+        if self.mbc.tick(&mut self.ram, cycles) {
+            self.dirty = true;
+        }
+    }
+
+    /// The contents of a battery save: the RAM followed by any extra state
+    /// of the bank controller.
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        data.extend(self.mbc.save_state());
+        data
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+        self.mbc.load_state(&data[len..]);
+    }
+}
+
+#[cfg(test)]
+impl Cartridge {
+    /// A cartridge of the given type with 32 KiB of empty ROM behind a valid
+    /// header.
+    pub fn blank(cartridge_type: u8) -> Self {
+        let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+        rom[0x104..0x134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x147] = cartridge_type;
+        rom[0x14D] = header_checksum(&rom);
+        let checksum = global_checksum(&rom);
+        rom[0x14E..0x150].copy_from_slice(&checksum.to_be_bytes());
+        Cartridge::new(rom).unwrap()
+    }
+}
@@ -3,9 +3,14 @@ const SUBTRACT_FLAG_POSITION: u8 = 6;
 const HALF_CARRY_FLAG_POSITION: u8 = 5;
 const CARRY_FLAG_POSITION: u8 = 4;
 
+/// Cycles the CPU is stalled for while the clock settles after a speed switch.
+const SPEED_SWITCH_CYCLES: usize = 8200;
+
+use crate::cartridge::Header;
 use crate::instruction::*;
+use crate::interrupts::Interrupt;
 use crate::memory_bus::MemoryBus;
-use std::io;
+use crate::model::Model;
 
 #[derive(Clone)]
 struct Flags {
@@ -67,16 +72,27 @@ impl std::fmt::Debug for Registers {
 }
 
 impl Registers {
-    fn new() -> Self {
+    /// The values the boot ROM of the given model leaves behind.
+    fn new(model: Model, header: &Header) -> Self {
+        let [af, bc, de, hl] = model.initial_registers(header);
+        let mut registers = Self::zeroed();
+        registers.set_af(af);
+        registers.set_bc(bc);
+        registers.set_de(de);
+        registers.set_hl(hl);
+        registers
+    }
+
+    fn zeroed() -> Self {
         Self {
-            a: 0x01,
-            f: 0xB0.into(),
-            b: 0x00,
-            c: 0x13,
-            d: 0x00,
-            e: 0xD8,
-            h: 0x01,
-            l: 0x4D,
+            a: 0,
+            f: 0.into(),
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
         }
     }
 
@@ -96,6 +112,11 @@ impl Registers {
         ((self.h as u16) << 8) | (self.l as u16)
     }
 
+    fn set_af(&mut self, value: u16) {
+        self.a = (value >> 8) as u8;
+        self.f = ((value & 0xFF) as u8).into();
+    }
+
     fn set_bc(&mut self, value: u16) {
         self.b = (value >> 8) as u8;
         self.c = (value & 0xFF) as u8;
@@ -114,31 +135,99 @@ pub struct CPU {
     pc: u16,
     sp: u16,
     registers: Registers,
-    bus: MemoryBus,
+    ime: bool,
+    ime_scheduled: bool,
+    halted: bool,
+    halt_bug: bool,
+    stopped: bool,
+    cycles: usize,
 }
 
 impl CPU {
-    pub fn new() -> Self {
+    /// A CPU in the state the boot ROM hands over to the cartridge in.
+    pub fn new(model: Model, header: &Header) -> Self {
         Self {
             pc: 0x0100,
             sp: 0xFFFE,
-            registers: Registers::new(),
-            bus: MemoryBus::new(),
+            registers: Registers::new(model, header),
+            ime: false,
+            ime_scheduled: false,
+            halted: false,
+            halt_bug: false,
+            stopped: false,
+            cycles: 0,
         }
     }
 
-    pub fn load<R: io::Read>(&mut self, data: &mut R) {
-        self.bus.load(data);
+    /// A CPU as it comes out of reset, about to run the boot ROM.
+    pub fn power_on() -> Self {
+        Self {
+            pc: 0x0000,
+            sp: 0x0000,
+            registers: Registers::zeroed(),
+            ime: false,
+            ime_scheduled: false,
+            halted: false,
+            halt_bug: false,
+            stopped: false,
+            cycles: 0,
+        }
     }
 
-    pub fn step(&mut self) -> Result<(), String> {
-        let byte = self.bus.read_byte(self.pc);
-        match Instruction::from_byte(byte) {
+    /// Executes a single instruction, or services an interrupt, and returns
+    /// the number of cycles it took. The bus has already been ticked for
+    /// every one of them by the time this returns.
+    pub fn step(&mut self, bus: &mut MemoryBus) -> Result<usize, String> {
+        self.cycles = 0;
+        if self.stopped {
+            // Only a joypad press brings the CPU out of STOP. The system clock
+            // is halted meanwhile, so nothing else on the bus runs either.
+            if !bus.joypad.selected_pressed() {
+                self.cycles += 4;
+                return Ok(self.cycles);
+            }
+            self.stopped = false;
+        }
+        if self.halted {
+            // HALT ends as soon as any enabled interrupt is requested, even
+            // with IME cleared
+            if bus.interrupts.next().is_none() {
+                self.tick(bus);
+                return Ok(self.cycles);
+            }
+            self.halted = false;
+        }
+        if self.ime {
+            if let Some(interrupt) = bus.interrupts.next() {
+                self.service_interrupt(bus, interrupt);
+                return Ok(self.cycles);
+            }
+        }
+        // EI takes effect after the instruction following it, which is the
+        // one about to run, so interrupts are only checked again next step.
+        if self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
+        }
+
+        let pc = self.pc;
+        let mut byte = self.read(bus, pc);
+        if self.halt_bug {
+            // PC fails to increment after the opcode fetch, so the byte
+            // following HALT is read twice
+            self.halt_bug = false;
+        } else {
+            self.pc = self.pc.wrapping_add(1);
+        }
+        let prefixed = byte == 0xCB;
+        if prefixed {
+            byte = self.fetch_byte(bus);
+        }
+        match Instruction::from_byte(byte, prefixed) {
             Ok(instruction) => {
-                trace!("{:04X}: {:?}", self.pc, instruction);
-                let (new_pc, _cycles) = self.execute(instruction);
-                self.pc = new_pc;
-                Ok(())
+                trace!("{:04X}: {:?}", pc, instruction);
+                self.execute(bus, instruction);
+                Ok(self.cycles)
             }
             Err(err) => {
                 debug!("{:?}", self.registers);
@@ -147,30 +236,55 @@ impl CPU {
         }
     }
 
-    fn get_bca(&self) -> u8 {
-        self.bus.read_byte(self.registers.get_bc())
+    /// Jumps to the interrupt's vector. Takes five M-cycles: two wait
+    /// states, the two pushes of PC (after the usual internal delay) and the
+    /// jump itself.
+    fn service_interrupt(&mut self, bus: &mut MemoryBus, interrupt: Interrupt) {
+        trace!("servicing {:?} interrupt", interrupt);
+        self.ime = false;
+        self.tick(bus);
+        bus.interrupts.acknowledge(interrupt);
+        self.push(bus, self.pc);
+        self.pc = interrupt.vector();
+        self.tick(bus);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Lets the rest of the system run for one M-cycle.
+    fn tick(&mut self, bus: &mut MemoryBus) {
+        self.cycles += 4;
+        bus.tick(4);
     }
 
-    fn get_dea(&self) -> u8 {
-        self.bus.read_byte(self.registers.get_de())
+    /// Reads a byte on its own M-cycle. Peripherals are advanced first so
+    /// the access sees their state at the end of that cycle.
+    fn read(&mut self, bus: &mut MemoryBus, address: u16) -> u8 {
+        self.tick(bus);
+        bus.read_byte(address)
     }
 
-    fn get_hla(&self) -> u8 {
-        self.bus.read_byte(self.registers.get_hl())
+    fn write(&mut self, bus: &mut MemoryBus, address: u16, value: u8) {
+        self.tick(bus);
+        bus.write_byte(address, value);
     }
 
-    fn immediate_byte(&self) -> u8 {
-        self.bus.read_byte(self.pc.wrapping_add(1))
+    fn fetch_byte(&mut self, bus: &mut MemoryBus) -> u8 {
+        let value = self.read(bus, self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        value
     }
 
-    fn immediate_word(&self) -> u16 {
-        self.bus.read_word(self.pc.wrapping_add(1))
+    fn fetch_word(&mut self, bus: &mut MemoryBus) -> u16 {
+        let low = self.fetch_byte(bus) as u16;
+        let high = self.fetch_byte(bus) as u16;
+        (high << 8) | low
     }
 
-    fn get_arithmetic_target(&self, target: ArithmeticTarget) -> (u8, usize, u16) {
-        let mut cycles = 4;
-        let mut consumed_bytes = 0;
-        let value = match target {
+    fn get_arithmetic_target(&mut self, bus: &mut MemoryBus, target: ArithmeticTarget) -> u8 {
+        match target {
             ArithmeticTarget::A => self.registers.a,
             ArithmeticTarget::B => self.registers.b,
             ArithmeticTarget::C => self.registers.c,
@@ -178,34 +292,124 @@ impl CPU {
             ArithmeticTarget::E => self.registers.e,
             ArithmeticTarget::H => self.registers.h,
             ArithmeticTarget::L => self.registers.l,
-            ArithmeticTarget::HLA => {
-                cycles = 8;
-                self.get_hla()
-            }
-            ArithmeticTarget::Immediate => {
-                cycles = 8;
-                let value = self.immediate_byte();
-                consumed_bytes = 1;
-                value
+            ArithmeticTarget::HLA => self.read(bus, self.registers.get_hl()),
+            ArithmeticTarget::Immediate => self.fetch_byte(bus),
+        }
+    }
+
+    fn get_inc_dec_target(&mut self, bus: &mut MemoryBus, target: &IncDecByteTarget) -> u8 {
+        match target {
+            IncDecByteTarget::A => self.registers.a,
+            IncDecByteTarget::B => self.registers.b,
+            IncDecByteTarget::C => self.registers.c,
+            IncDecByteTarget::D => self.registers.d,
+            IncDecByteTarget::E => self.registers.e,
+            IncDecByteTarget::H => self.registers.h,
+            IncDecByteTarget::L => self.registers.l,
+            IncDecByteTarget::HLA => self.read(bus, self.registers.get_hl()),
+        }
+    }
+
+    fn set_inc_dec_target(&mut self, bus: &mut MemoryBus, target: IncDecByteTarget, value: u8) {
+        match target {
+            IncDecByteTarget::A => self.registers.a = value,
+            IncDecByteTarget::B => self.registers.b = value,
+            IncDecByteTarget::C => self.registers.c = value,
+            IncDecByteTarget::D => self.registers.d = value,
+            IncDecByteTarget::E => self.registers.e = value,
+            IncDecByteTarget::H => self.registers.h = value,
+            IncDecByteTarget::L => self.registers.l = value,
+            IncDecByteTarget::HLA => {
+                self.write(bus, self.registers.get_hl(), value);
             }
-        };
-        (value, cycles, consumed_bytes)
+        }
+    }
+
+    fn get_prefix_target(&mut self, bus: &mut MemoryBus, target: &PrefixTarget) -> u8 {
+        match target {
+            PrefixTarget::A => self.registers.a,
+            PrefixTarget::B => self.registers.b,
+            PrefixTarget::C => self.registers.c,
+            PrefixTarget::D => self.registers.d,
+            PrefixTarget::E => self.registers.e,
+            PrefixTarget::H => self.registers.h,
+            PrefixTarget::L => self.registers.l,
+            PrefixTarget::HLA => self.read(bus, self.registers.get_hl()),
+        }
+    }
+
+    fn set_prefix_target(&mut self, bus: &mut MemoryBus, target: PrefixTarget, value: u8) {
+        match target {
+            PrefixTarget::A => self.registers.a = value,
+            PrefixTarget::B => self.registers.b = value,
+            PrefixTarget::C => self.registers.c = value,
+            PrefixTarget::D => self.registers.d = value,
+            PrefixTarget::E => self.registers.e = value,
+            PrefixTarget::H => self.registers.h = value,
+            PrefixTarget::L => self.registers.l = value,
+            PrefixTarget::HLA => self.write(bus, self.registers.get_hl(), value),
+        }
+    }
+
+    fn set_shift_flags(&mut self, result: u8, carry: bool) {
+        self.registers.f.zero = result == 0;
+        self.registers.f.subtract = false;
+        self.registers.f.half_carry = false;
+        self.registers.f.carry = carry;
+    }
+
+    fn check_condition(&self, condition: JumpCondition) -> bool {
+        match condition {
+            JumpCondition::Carry => self.registers.f.carry,
+            JumpCondition::NotCarry => !self.registers.f.carry,
+            JumpCondition::Zero => self.registers.f.zero,
+            JumpCondition::NotZero => !self.registers.f.zero,
+        }
     }
 
-    fn execute(&mut self, instruction: Instruction) -> (u16, usize) {
-        let mut next_pc = self.pc.wrapping_add(1);
-        let cycles = match instruction {
-            Instruction::Nop => 4,
+    /// Pushes a word onto the stack, high byte first like the hardware does.
+    /// SP is decremented on an internal cycle before the two writes.
+    fn push(&mut self, bus: &mut MemoryBus, value: u16) {
+        self.tick(bus);
+        self.sp = self.sp.wrapping_sub(1);
+        self.write(bus, self.sp, (value >> 8) as u8);
+        self.sp = self.sp.wrapping_sub(1);
+        self.write(bus, self.sp, (value & 0xFF) as u8);
+    }
+
+    /// Pops a word off the stack, low byte first.
+    fn pop(&mut self, bus: &mut MemoryBus) -> u16 {
+        let low = self.read(bus, self.sp) as u16;
+        self.sp = self.sp.wrapping_add(1);
+        let high = self.read(bus, self.sp) as u16;
+        self.sp = self.sp.wrapping_add(1);
+        (high << 8) | low
+    }
+
+    /// Adds the signed immediate byte to SP, setting the flags the way both
+    /// `ADD SP, r8` and `LD HL, SP+r8` do.
+    fn sp_plus_immediate(&mut self, bus: &mut MemoryBus) -> u16 {
+        let offset = self.fetch_byte(bus);
+        let sp = self.sp;
+        self.registers.f.clear();
+        self.registers.f.half_carry = (sp & 0xF) + (offset as u16 & 0xF) > 0xF;
+        self.registers.f.carry = (sp & 0xFF) + (offset as u16) > 0xFF;
+        sp.wrapping_add(offset as i8 as u16)
+    }
+
+    /// Executes an instruction whose opcode has already been fetched. Every
+    /// further memory access and internal delay ticks the bus on its own.
+    fn execute(&mut self, bus: &mut MemoryBus, instruction: Instruction) {
+        match instruction {
+            Instruction::Nop => {}
             Instruction::Add(target) => {
-                let (value, cycles, consumed_bytes) = self.get_arithmetic_target(target);
-                next_pc += consumed_bytes;
+                let value = self.get_arithmetic_target(bus, target);
                 let (result, overflow) = self.registers.a.overflowing_add(value);
                 self.registers.f.zero = result == 0;
                 self.registers.f.subtract = false;
-                self.registers.f.half_carry = (self.registers.a & 0xF) + (result & 0xF) > 0xF;
+                self.registers.f.half_carry = (self.registers.a & 0xF) + (value & 0xF) > 0xF;
                 self.registers.f.carry = overflow;
                 self.registers.a = result;
-                cycles
             }
             Instruction::AddHl(target) => {
                 let value = match target {
@@ -217,193 +421,272 @@ impl CPU {
                 let hl = self.registers.get_hl();
                 let (result, overflow) = hl.overflowing_add(value);
                 self.registers.f.subtract = false;
-                self.registers.f.half_carry = (hl & 0xFF) + (result & 0xFF) > 0xFF;
+                self.registers.f.half_carry = (hl & 0xFFF) + (value & 0xFFF) > 0xFFF;
                 self.registers.f.carry = overflow;
-                8
+                self.registers.set_hl(result);
+                self.tick(bus);
+            }
+            Instruction::AddSp => {
+                self.sp = self.sp_plus_immediate(bus);
+                self.tick(bus);
+                self.tick(bus);
             }
             Instruction::Adc(target) => {
-                let (value, cycles, consumed_bytes) = self.get_arithmetic_target(target);
-                next_pc += consumed_bytes;
-                let (result, overflow) = self.registers.a.overflowing_add(value);
+                let value = self.get_arithmetic_target(bus, target);
+                let carry = self.registers.f.carry as u8;
+                let result = self.registers.a.wrapping_add(value).wrapping_add(carry);
                 self.registers.f.zero = result == 0;
                 self.registers.f.subtract = false;
-                self.registers.f.carry = overflow;
-                self.registers.f.half_carry = (self.registers.a & 0xF) + (result & 0xF) > 0xF;
-                self.registers.a = result.wrapping_add(overflow as u8);
-                cycles
+                self.registers.f.half_carry =
+                    (self.registers.a & 0xF) + (value & 0xF) + carry > 0xF;
+                self.registers.f.carry =
+                    (self.registers.a as u16) + (value as u16) + (carry as u16) > 0xFF;
+                self.registers.a = result;
+            }
+            Instruction::Sub(target) => {
+                let value = self.get_arithmetic_target(bus, target);
+                let result = self.registers.a.wrapping_sub(value);
+                self.registers.f.zero = result == 0;
+                self.registers.f.subtract = true;
+                self.registers.f.half_carry = (self.registers.a & 0xF) < (value & 0xF);
+                self.registers.f.carry = self.registers.a < value;
+                self.registers.a = result;
+            }
+            Instruction::Sbc(target) => {
+                let value = self.get_arithmetic_target(bus, target);
+                let carry = self.registers.f.carry as u8;
+                let result = self.registers.a.wrapping_sub(value).wrapping_sub(carry);
+                self.registers.f.zero = result == 0;
+                self.registers.f.subtract = true;
+                self.registers.f.half_carry = (self.registers.a & 0xF) < (value & 0xF) + carry;
+                self.registers.f.carry =
+                    (self.registers.a as u16) < (value as u16) + (carry as u16);
+                self.registers.a = result;
+            }
+            Instruction::And(target) => {
+                let value = self.get_arithmetic_target(bus, target);
+                self.registers.a &= value;
+                self.registers.f.clear();
+                self.registers.f.zero = self.registers.a == 0;
+                self.registers.f.half_carry = true;
             }
             Instruction::Or(target) => {
-                let (value, cycles, consumed_bytes) = self.get_arithmetic_target(target);
-                next_pc += consumed_bytes;
+                let value = self.get_arithmetic_target(bus, target);
                 self.registers.a |= value;
                 self.registers.f.clear();
                 self.registers.f.zero = self.registers.a == 0;
-                cycles
             }
             Instruction::Xor(target) => {
-                let (value, cycles, consumed_bytes) = self.get_arithmetic_target(target);
-                next_pc += consumed_bytes;
+                let value = self.get_arithmetic_target(bus, target);
                 self.registers.a ^= value;
                 self.registers.f.zero = self.registers.a == 0;
                 self.registers.f.subtract = false;
                 self.registers.f.half_carry = false;
                 self.registers.f.carry = false;
-                cycles
             }
             Instruction::Cp(target) => {
-                let (value, cycles, consumed_bytes) = self.get_arithmetic_target(target);
-                next_pc += consumed_bytes;
+                let value = self.get_arithmetic_target(bus, target);
                 self.registers.f.zero = value == self.registers.a;
                 self.registers.f.subtract = true;
-                self.registers.f.half_carry =
-                    (self.registers.a & 0xF) + (self.registers.a.wrapping_sub(value) & 0xF) > 0xF;
+                self.registers.f.half_carry = (self.registers.a & 0xF) < (value & 0xF);
                 self.registers.f.carry = self.registers.a < value;
-                cycles
             }
             Instruction::Inc(IncDecType::Byte(target)) => {
-                let mut cycles = 4;
-                let register = match target {
-                    IncDecByteTarget::A => &mut self.registers.a,
-                    IncDecByteTarget::B => &mut self.registers.b,
-                    IncDecByteTarget::C => &mut self.registers.c,
-                    IncDecByteTarget::D => &mut self.registers.d,
-                    IncDecByteTarget::E => &mut self.registers.e,
-                    IncDecByteTarget::H => &mut self.registers.h,
-                    IncDecByteTarget::L => &mut self.registers.l,
-                    IncDecByteTarget::HLA => {
-                        cycles = 12;
-                        let hl = ((self.registers.h as u16) << 8) | (self.registers.l as u16);
-                        self.bus.get_mut_byte(hl)
-                    }
-                };
-                let result = register.wrapping_add(1);
+                let value = self.get_inc_dec_target(bus, &target);
+                let result = value.wrapping_add(1);
                 self.registers.f.zero = result == 0;
                 self.registers.f.subtract = false;
-                self.registers.f.half_carry = (*register & 0xF) + (result & 0xF) & 0x10 != 0;
-                *register = result;
-                cycles
-            }
-            Instruction::Inc(IncDecType::Word(IncDecWordTarget::SP)) => {
-                self.sp = self.sp.wrapping_add(1);
-                8
+                self.registers.f.half_carry = value & 0xF == 0xF;
+                self.set_inc_dec_target(bus, target, result);
             }
             Instruction::Inc(IncDecType::Word(target)) => {
-                let (high, low) = match target {
-                    IncDecWordTarget::BC => (&mut self.registers.b, &mut self.registers.c),
-                    IncDecWordTarget::DE => (&mut self.registers.d, &mut self.registers.e),
-                    IncDecWordTarget::HL => (&mut self.registers.h, &mut self.registers.l),
-                    IncDecWordTarget::SP => unreachable!(),
-                };
-                let combined = ((*high as u16) << 8) | (*low as u16);
-                let result = combined.wrapping_add(1);
-                *high = (result >> 8) as u8;
-                *low = (result & 0xFF) as u8;
-                8
+                match target {
+                    IncDecWordTarget::BC => {
+                        self.registers
+                            .set_bc(self.registers.get_bc().wrapping_add(1));
+                    }
+                    IncDecWordTarget::DE => {
+                        self.registers
+                            .set_de(self.registers.get_de().wrapping_add(1));
+                    }
+                    IncDecWordTarget::HL => {
+                        self.registers
+                            .set_hl(self.registers.get_hl().wrapping_add(1));
+                    }
+                    IncDecWordTarget::SP => self.sp = self.sp.wrapping_add(1),
+                }
+                self.tick(bus);
             }
             Instruction::Dec(IncDecType::Byte(target)) => {
-                let mut cycles = 4;
-                let register = match target {
-                    IncDecByteTarget::A => &mut self.registers.a,
-                    IncDecByteTarget::B => &mut self.registers.b,
-                    IncDecByteTarget::C => &mut self.registers.c,
-                    IncDecByteTarget::D => &mut self.registers.d,
-                    IncDecByteTarget::E => &mut self.registers.e,
-                    IncDecByteTarget::H => &mut self.registers.h,
-                    IncDecByteTarget::L => &mut self.registers.l,
-                    IncDecByteTarget::HLA => {
-                        cycles = 12;
-                        let hl = ((self.registers.h as u16) << 8) | (self.registers.l as u16);
-                        self.bus.get_mut_byte(hl)
-                    }
-                };
-                let result = register.wrapping_sub(1);
+                let value = self.get_inc_dec_target(bus, &target);
+                let result = value.wrapping_sub(1);
                 self.registers.f.zero = result == 0;
                 self.registers.f.subtract = true;
-                self.registers.f.half_carry =
-                    ((*register & 0xF) as i8) - ((result & 0xF) as i8) < 0;
-                *register = result;
-                cycles
-            }
-            Instruction::Dec(IncDecType::Word(IncDecWordTarget::SP)) => {
-                self.sp = self.sp.wrapping_sub(1);
-                8
+                self.registers.f.half_carry = value & 0xF == 0;
+                self.set_inc_dec_target(bus, target, result);
             }
             Instruction::Dec(IncDecType::Word(target)) => {
-                let (high, low) = match target {
-                    IncDecWordTarget::BC => (&mut self.registers.b, &mut self.registers.c),
-                    IncDecWordTarget::DE => (&mut self.registers.d, &mut self.registers.e),
-                    IncDecWordTarget::HL => (&mut self.registers.h, &mut self.registers.l),
-                    IncDecWordTarget::SP => unreachable!(),
-                };
-                let combined = ((*high as u16) << 8) | (*low as u16);
-                let result = combined.wrapping_add(1);
-                *high = (result >> 8) as u8;
-                *low = (result & 0xFF) as u8;
-                8
+                match target {
+                    IncDecWordTarget::BC => {
+                        self.registers
+                            .set_bc(self.registers.get_bc().wrapping_sub(1));
+                    }
+                    IncDecWordTarget::DE => {
+                        self.registers
+                            .set_de(self.registers.get_de().wrapping_sub(1));
+                    }
+                    IncDecWordTarget::HL => {
+                        self.registers
+                            .set_hl(self.registers.get_hl().wrapping_sub(1));
+                    }
+                    IncDecWordTarget::SP => self.sp = self.sp.wrapping_sub(1),
+                }
+                self.tick(bus);
+            }
+            Instruction::Rlca => {
+                let msb = self.registers.a >> 7;
+                self.registers.a = self.registers.a.rotate_left(1);
+                self.registers.f.clear();
+                self.registers.f.carry = msb == 1;
+            }
+            Instruction::Rrca => {
+                let lsb = self.registers.a & 1;
+                self.registers.a = self.registers.a.rotate_right(1);
+                self.registers.f.clear();
+                self.registers.f.carry = lsb == 1;
             }
             Instruction::Rra => {
                 let lsb = self.registers.a & 1;
                 self.registers.a >>= 1;
-                self.registers.a &= !(1 << 7);
                 self.registers.a |= (self.registers.f.carry as u8) << 7;
                 self.registers.f.clear();
                 self.registers.f.carry = lsb == 1;
-                4
             }
             Instruction::Rla => {
-                let msb = self.registers.a & (1 << 7);
+                let msb = self.registers.a >> 7;
                 self.registers.a <<= 1;
-                self.registers.a &= !1;
                 self.registers.a |= self.registers.f.carry as u8;
                 self.registers.f.clear();
                 self.registers.f.carry = msb == 1;
-                4
             }
-            Instruction::Jp(Jump::Always(target)) => {
-                next_pc = match target {
-                    JumpTarget::Immediate => self.bus.read_word(next_pc),
-                    JumpTarget::HLA => self.bus.read_word(self.registers.get_hl()),
-                };
-                16
+            Instruction::Daa => {
+                let mut a = self.registers.a;
+                let mut carry = self.registers.f.carry;
+                if self.registers.f.subtract {
+                    if self.registers.f.half_carry {
+                        a = a.wrapping_sub(0x06);
+                    }
+                    if carry {
+                        a = a.wrapping_sub(0x60);
+                    }
+                } else {
+                    if carry || a > 0x99 {
+                        a = a.wrapping_add(0x60);
+                        carry = true;
+                    }
+                    if self.registers.f.half_carry || (a & 0xF) > 0x9 {
+                        a = a.wrapping_add(0x06);
+                    }
+                }
+                self.registers.a = a;
+                self.registers.f.zero = a == 0;
+                self.registers.f.half_carry = false;
+                self.registers.f.carry = carry;
+            }
+            Instruction::Cpl => {
+                self.registers.a = !self.registers.a;
+                self.registers.f.subtract = true;
+                self.registers.f.half_carry = true;
+            }
+            Instruction::Scf => {
+                self.registers.f.subtract = false;
+                self.registers.f.half_carry = false;
+                self.registers.f.carry = true;
+            }
+            Instruction::Ccf => {
+                self.registers.f.subtract = false;
+                self.registers.f.half_carry = false;
+                self.registers.f.carry = !self.registers.f.carry;
+            }
+            Instruction::Jp(Jump::Always(JumpTarget::Immediate)) => {
+                self.pc = self.fetch_word(bus);
+                self.tick(bus);
+            }
+            Instruction::Jp(Jump::Always(JumpTarget::HLA)) => {
+                self.pc = self.registers.get_hl();
             }
             Instruction::Jp(Jump::Conditional(jump_condition)) => {
-                let condition = match jump_condition {
-                    JumpCondition::Carry => self.registers.f.carry,
-                    JumpCondition::NotCarry => !self.registers.f.carry,
-                    JumpCondition::Zero => self.registers.f.zero,
-                    JumpCondition::NotZero => !self.registers.f.zero,
-                };
-                if condition {
-                    next_pc = next_pc.wrapping_add(self.bus.read_word(next_pc));
-                    16
-                } else {
-                    next_pc = next_pc.wrapping_add(2);
-                    12
+                let addr = self.fetch_word(bus);
+                if self.check_condition(jump_condition) {
+                    self.pc = addr;
+                    self.tick(bus);
                 }
             }
             Instruction::Jr(JumpRelative::Always) => {
-                next_pc = next_pc.wrapping_add(self.bus.read_byte(next_pc) as u16);
-                12
+                let relative_addr = self.fetch_byte(bus) as i8;
+                self.pc = self.pc.wrapping_add(relative_addr as u16);
+                self.tick(bus);
             }
             Instruction::Jr(JumpRelative::Conditional(jump_condition)) => {
-                let condition = match jump_condition {
-                    JumpCondition::Carry => self.registers.f.carry,
-                    JumpCondition::NotCarry => !self.registers.f.carry,
-                    JumpCondition::Zero => self.registers.f.zero,
-                    JumpCondition::NotZero => !self.registers.f.zero,
+                let relative_addr = self.fetch_byte(bus) as i8;
+                if self.check_condition(jump_condition) {
+                    self.pc = self.pc.wrapping_add(relative_addr as u16);
+                    self.tick(bus);
+                }
+            }
+            Instruction::Call(Call::Always) => {
+                let addr = self.fetch_word(bus);
+                self.push(bus, self.pc);
+                self.pc = addr;
+            }
+            Instruction::Call(Call::Conditional(jump_condition)) => {
+                let addr = self.fetch_word(bus);
+                if self.check_condition(jump_condition) {
+                    self.push(bus, self.pc);
+                    self.pc = addr;
+                }
+            }
+            Instruction::Ret(Return::Always) => {
+                self.pc = self.pop(bus);
+                self.tick(bus);
+            }
+            Instruction::Ret(Return::Conditional(jump_condition)) => {
+                // Evaluating the condition costs an extra cycle
+                self.tick(bus);
+                if self.check_condition(jump_condition) {
+                    self.pc = self.pop(bus);
+                    self.tick(bus);
+                }
+            }
+            Instruction::Reti => {
+                self.ime = true;
+                self.pc = self.pop(bus);
+                self.tick(bus);
+            }
+            Instruction::Rst(vector) => {
+                self.push(bus, self.pc);
+                self.pc = vector as u16;
+            }
+            Instruction::Push(target) => {
+                let value = match target {
+                    StackTarget::AF => self.registers.get_af(),
+                    StackTarget::BC => self.registers.get_bc(),
+                    StackTarget::DE => self.registers.get_de(),
+                    StackTarget::HL => self.registers.get_hl(),
                 };
-                if condition {
-                    let relative_addr = self.bus.read_byte(next_pc) as i8;
-                    next_pc = next_pc.wrapping_add(1);
-                    next_pc = (next_pc as i16).wrapping_add(relative_addr as i16) as u16;
-                    12
-                } else {
-                    next_pc = next_pc.wrapping_add(1);
-                    8
+                self.push(bus, value);
+            }
+            Instruction::Pop(target) => {
+                let value = self.pop(bus);
+                match target {
+                    // The low nibble of F doesn't exist and always reads 0
+                    StackTarget::AF => self.registers.set_af(value),
+                    StackTarget::BC => self.registers.set_bc(value),
+                    StackTarget::DE => self.registers.set_de(value),
+                    StackTarget::HL => self.registers.set_hl(value),
                 }
             }
             Instruction::Ld(LoadType::Byte(byte_target, byte_source)) => {
-                let mut cycles = 4;
                 let source = match byte_source {
                     LoadByteSource::A => self.registers.a,
                     LoadByteSource::B => self.registers.b,
@@ -412,113 +695,370 @@ impl CPU {
                     LoadByteSource::E => self.registers.e,
                     LoadByteSource::H => self.registers.h,
                     LoadByteSource::L => self.registers.l,
-                    LoadByteSource::Immediate => {
-                        let data = self.immediate_byte();
-                        next_pc += 1;
-                        cycles += 4;
-                        data
-                    }
+                    LoadByteSource::Immediate => self.fetch_byte(bus),
                     LoadByteSource::ImmediateAddress => {
-                        let addr = self.immediate_word();
-                        next_pc += 2;
-                        cycles += 12;
-                        self.bus.read_byte(addr)
-                    }
-                    LoadByteSource::CA => {
-                        cycles += 4;
-                        self.bus.read_byte(self.registers.a.into())
+                        let addr = self.fetch_word(bus);
+                        self.read(bus, addr)
                     }
-                    LoadByteSource::BCA => {
-                        cycles += 4;
-                        self.get_bca()
-                    }
-                    LoadByteSource::DEA => {
-                        cycles += 4;
-                        self.get_dea()
-                    }
-                    LoadByteSource::HLA => {
-                        cycles += 4;
-                        self.get_hla()
+                    LoadByteSource::ImmediateHighAddress => {
+                        let addr = 0xFF00 | self.fetch_byte(bus) as u16;
+                        self.read(bus, addr)
                     }
+                    LoadByteSource::CA => self.read(bus, 0xFF00 | self.registers.c as u16),
+                    LoadByteSource::BCA => self.read(bus, self.registers.get_bc()),
+                    LoadByteSource::DEA => self.read(bus, self.registers.get_de()),
+                    LoadByteSource::HLA => self.read(bus, self.registers.get_hl()),
                     LoadByteSource::HLIA => {
-                        cycles += 4;
-                        let data = self.get_hla();
+                        let data = self.read(bus, self.registers.get_hl());
                         self.registers
                             .set_hl(self.registers.get_hl().wrapping_add(1));
                         data
                     }
                     LoadByteSource::HLDA => {
-                        cycles += 4;
-                        let data = self.get_hla();
+                        let data = self.read(bus, self.registers.get_hl());
                         self.registers
                             .set_hl(self.registers.get_hl().wrapping_sub(1));
                         data
                     }
-                }
-                .clone();
-                let target = match byte_target {
-                    LoadByteTarget::A => &mut self.registers.a,
-                    LoadByteTarget::B => &mut self.registers.b,
-                    LoadByteTarget::C => &mut self.registers.c,
-                    LoadByteTarget::D => &mut self.registers.d,
-                    LoadByteTarget::E => &mut self.registers.e,
-                    LoadByteTarget::H => &mut self.registers.h,
-                    LoadByteTarget::L => &mut self.registers.l,
+                };
+                match byte_target {
+                    LoadByteTarget::A => self.registers.a = source,
+                    LoadByteTarget::B => self.registers.b = source,
+                    LoadByteTarget::C => self.registers.c = source,
+                    LoadByteTarget::D => self.registers.d = source,
+                    LoadByteTarget::E => self.registers.e = source,
+                    LoadByteTarget::H => self.registers.h = source,
+                    LoadByteTarget::L => self.registers.l = source,
                     LoadByteTarget::ImmediateAddress => {
-                        cycles += 12;
-                        let addr = self.immediate_word();
-                        next_pc += 2;
-                        self.bus.get_mut_byte(addr)
+                        let addr = self.fetch_word(bus);
+                        self.write(bus, addr, source);
+                    }
+                    LoadByteTarget::ImmediateHighAddress => {
+                        let addr = 0xFF00 | self.fetch_byte(bus) as u16;
+                        self.write(bus, addr, source);
+                    }
+                    LoadByteTarget::CA => {
+                        self.write(bus, 0xFF00 | self.registers.c as u16, source);
                     }
                     LoadByteTarget::BCA => {
-                        cycles += 4;
-                        self.bus.get_mut_byte(self.registers.get_bc())
+                        self.write(bus, self.registers.get_bc(), source);
                     }
                     LoadByteTarget::DEA => {
-                        cycles += 4;
-                        self.bus.get_mut_byte(self.registers.get_de())
+                        self.write(bus, self.registers.get_de(), source);
                     }
                     LoadByteTarget::HLA => {
-                        cycles += 4;
-                        self.bus.get_mut_byte(self.registers.get_hl())
+                        self.write(bus, self.registers.get_hl(), source);
                     }
                     LoadByteTarget::HLIA => {
-                        cycles += 4;
-                        let data = self.bus.get_mut_byte(self.registers.get_hl());
+                        self.write(bus, self.registers.get_hl(), source);
                         self.registers
                             .set_hl(self.registers.get_hl().wrapping_add(1));
-                        data
                     }
                     LoadByteTarget::HLDA => {
-                        cycles += 4;
-                        let data = self.bus.get_mut_byte(self.registers.get_hl());
+                        self.write(bus, self.registers.get_hl(), source);
                         self.registers
                             .set_hl(self.registers.get_hl().wrapping_sub(1));
-                        data
                     }
                 };
-
-                *target = source;
-
-                cycles
             }
             Instruction::Ld(LoadType::Word(source)) => {
-                let value = self.immediate_word();
-                next_pc += 2;
+                let value = self.fetch_word(bus);
                 match source {
                     WordRegister::BC => self.registers.set_bc(value),
                     WordRegister::DE => self.registers.set_de(value),
                     WordRegister::HL => self.registers.set_hl(value),
                     WordRegister::SP => self.sp = value,
                 }
-                12
+            }
+            Instruction::Ld(LoadType::AddressFromSp) => {
+                let addr = self.fetch_word(bus);
+                self.write(bus, addr, (self.sp & 0xFF) as u8);
+                self.write(bus, addr.wrapping_add(1), (self.sp >> 8) as u8);
+            }
+            Instruction::Ld(LoadType::SpFromHl) => {
+                self.sp = self.registers.get_hl();
+                self.tick(bus);
+            }
+            Instruction::Ld(LoadType::HlFromSpOffset) => {
+                let value = self.sp_plus_immediate(bus);
+                self.registers.set_hl(value);
+                self.tick(bus);
             }
             Instruction::Di => {
-                // TODO: Disable interrupts
-                4
+                self.ime = false;
+                self.ime_scheduled = false;
             }
-        };
+            Instruction::Ei => {
+                self.ime_scheduled = true;
+            }
+            Instruction::Halt => {
+                if !self.ime && bus.interrupts.next().is_some() {
+                    self.halt_bug = true;
+                } else {
+                    self.halted = true;
+                }
+            }
+            Instruction::Rlc(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = value.rotate_left(1);
+                self.set_shift_flags(result, value & 0x80 != 0);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Rrc(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = value.rotate_right(1);
+                self.set_shift_flags(result, value & 0x01 != 0);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Rl(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = (value << 1) | self.registers.f.carry as u8;
+                self.set_shift_flags(result, value & 0x80 != 0);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Rr(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = (value >> 1) | ((self.registers.f.carry as u8) << 7);
+                self.set_shift_flags(result, value & 0x01 != 0);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Sla(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = value << 1;
+                self.set_shift_flags(result, value & 0x80 != 0);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Sra(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = (value >> 1) | (value & 0x80);
+                self.set_shift_flags(result, value & 0x01 != 0);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Swap(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = value.rotate_left(4);
+                self.set_shift_flags(result, false);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Srl(target) => {
+                let value = self.get_prefix_target(bus, &target);
+                let result = value >> 1;
+                self.set_shift_flags(result, value & 0x01 != 0);
+                self.set_prefix_target(bus, target, result);
+            }
+            Instruction::Bit(bit, target) => {
+                let value = self.get_prefix_target(bus, &target);
+                self.registers.f.zero = value & (1 << bit) == 0;
+                self.registers.f.subtract = false;
+                self.registers.f.half_carry = true;
+            }
+            Instruction::Res(bit, target) => {
+                let value = self.get_prefix_target(bus, &target);
+                self.set_prefix_target(bus, target, value & !(1 << bit));
+            }
+            Instruction::Set(bit, target) => {
+                let value = self.get_prefix_target(bus, &target);
+                self.set_prefix_target(bus, target, value | (1 << bit));
+            }
+            Instruction::Stop => {
+                // STOP is followed by a padding byte
+                self.pc = self.pc.wrapping_add(1);
+                // Entering STOP resets the divider
+                bus.write_byte(0xFF04, 0);
+                if bus.speed_switch_armed() {
+                    bus.switch_speed();
+                    for _ in 0..SPEED_SWITCH_CYCLES / 4 {
+                        self.tick(bus);
+                    }
+                } else {
+                    self.stopped = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::Cartridge;
+
+    const PROGRAM_START: u16 = 0xC000;
+    const ZERO: u8 = 1 << ZERO_FLAG_POSITION;
+    const SUBTRACT: u8 = 1 << SUBTRACT_FLAG_POSITION;
+    const HALF_CARRY: u8 = 1 << HALF_CARRY_FLAG_POSITION;
+    const CARRY: u8 = 1 << CARRY_FLAG_POSITION;
+
+    /// Runs the program from work RAM until PC reaches `end`, which is an
+    /// offset into the program.
+    fn run_until(program: &[u8], end: u16) -> (CPU, MemoryBus) {
+        let mut bus = MemoryBus::power_on(Model::Dmg, Cartridge::blank(0x00));
+        for (offset, byte) in program.iter().enumerate() {
+            bus.write_byte(PROGRAM_START + offset as u16, *byte);
+        }
+        let mut cpu = CPU::power_on();
+        cpu.pc = PROGRAM_START;
+        cpu.sp = 0xDFFE;
+        for _ in 0..1000 {
+            if cpu.pc == PROGRAM_START + end {
+                return (cpu, bus);
+            }
+            cpu.step(&mut bus).unwrap();
+        }
+        panic!("program didn't reach {:04X}", PROGRAM_START + end);
+    }
+
+    fn run(program: &[u8]) -> CPU {
+        run_until(program, program.len() as u16).0
+    }
+
+    fn flags(cpu: &CPU) -> u8 {
+        u8::from(cpu.registers.f.clone())
+    }
+
+    #[test]
+    fn daa_after_addition() {
+        // LD A, 0x45; ADD A, 0x38; DAA
+        let cpu = run(&[0x3E, 0x45, 0xC6, 0x38, 0x27]);
+        assert_eq!(cpu.registers.a, 0x83);
+        assert_eq!(flags(&cpu), 0);
+
+        // LD A, 0x99; ADD A, 0x01; DAA
+        let cpu = run(&[0x3E, 0x99, 0xC6, 0x01, 0x27]);
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_eq!(flags(&cpu), ZERO | CARRY);
+    }
+
+    #[test]
+    fn daa_after_subtraction() {
+        // LD A, 0x83; SUB 0x38; DAA
+        let cpu = run(&[0x3E, 0x83, 0xD6, 0x38, 0x27]);
+        assert_eq!(cpu.registers.a, 0x45);
+        assert_eq!(flags(&cpu), SUBTRACT);
+    }
+
+    #[test]
+    fn add_sp_immediate() {
+        // LD SP, 0x00FF; ADD SP, 1
+        let cpu = run(&[0x31, 0xFF, 0x00, 0xE8, 0x01]);
+        assert_eq!(cpu.sp, 0x0100);
+        assert_eq!(flags(&cpu), HALF_CARRY | CARRY);
+
+        // LD SP, 0x0000; ADD SP, -1 carries from neither nibble nor byte
+        let cpu = run(&[0x31, 0x00, 0x00, 0xE8, 0xFF]);
+        assert_eq!(cpu.sp, 0xFFFF);
+        assert_eq!(flags(&cpu), 0);
+    }
+
+    #[test]
+    fn load_hl_sp_plus_immediate() {
+        // XOR A; LD SP, 0xFFF8; LD HL, SP+8
+        let cpu = run(&[0xAF, 0x31, 0xF8, 0xFF, 0xF8, 0x08]);
+        assert_eq!(cpu.registers.get_hl(), 0x0000);
+        assert_eq!(cpu.sp, 0xFFF8);
+        // Z is always cleared, even for a zero result
+        assert_eq!(flags(&cpu), HALF_CARRY | CARRY);
+    }
+
+    #[test]
+    fn subtract_with_carry_in() {
+        // SCF; LD A, 0x10; SBC A, 0x0F
+        let cpu = run(&[0x37, 0x3E, 0x10, 0xDE, 0x0F]);
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_eq!(flags(&cpu), ZERO | SUBTRACT | HALF_CARRY);
+
+        // SCF; LD A, 0x00; SBC A, 0x00
+        let cpu = run(&[0x37, 0x3E, 0x00, 0xDE, 0x00]);
+        assert_eq!(cpu.registers.a, 0xFF);
+        assert_eq!(flags(&cpu), SUBTRACT | HALF_CARRY | CARRY);
+    }
+
+    #[test]
+    fn add_with_carry_in() {
+        // SCF; LD A, 0x0F; ADC A, 0x00
+        let cpu = run(&[0x37, 0x3E, 0x0F, 0xCE, 0x00]);
+        assert_eq!(cpu.registers.a, 0x10);
+        assert_eq!(flags(&cpu), HALF_CARRY);
+    }
+
+    #[test]
+    fn add_hl_stores_result_and_keeps_zero() {
+        // XOR A; LD HL, 0x0FFF; LD BC, 0x0001; ADD HL, BC
+        let cpu = run(&[0xAF, 0x21, 0xFF, 0x0F, 0x01, 0x01, 0x00, 0x09]);
+        assert_eq!(cpu.registers.get_hl(), 0x1000);
+        assert_eq!(flags(&cpu), ZERO | HALF_CARRY);
+    }
+
+    #[test]
+    fn word_decrement() {
+        // XOR A; LD BC, 0x0000; DEC BC
+        let cpu = run(&[0xAF, 0x01, 0x00, 0x00, 0x0B]);
+        assert_eq!(cpu.registers.get_bc(), 0xFFFF);
+        // 16-bit INC and DEC leave the flags alone
+        assert_eq!(flags(&cpu), ZERO);
+    }
+
+    #[test]
+    fn load_high_page_through_c() {
+        // LD A, 0x42; LD C, 0x80; LD (C), A; LD A, 0; LD A, (C)
+        let cpu = run(&[0x3E, 0x42, 0x0E, 0x80, 0xE2, 0x3E, 0x00, 0xF2]);
+        assert_eq!(cpu.registers.a, 0x42);
+    }
+
+    #[test]
+    fn pop_af_masks_low_nibble() {
+        // LD BC, 0x12FF; PUSH BC; POP AF
+        let cpu = run(&[0x01, 0xFF, 0x12, 0xC5, 0xF1]);
+        assert_eq!(cpu.registers.a, 0x12);
+        assert_eq!(flags(&cpu), 0xF0);
+        assert_eq!(cpu.sp, 0xDFFE);
+    }
+
+    #[test]
+    fn call_and_return() {
+        // CALL 0xC010, followed by LD A, 0x77; RET at 0xC010
+        let mut program = vec![0xCD, 0x10, 0xC0];
+        program.resize(0x10, 0x00);
+        program.extend_from_slice(&[0x3E, 0x77, 0xC9]);
+        let (cpu, bus) = run_until(&program, 0x03);
+        assert_eq!(cpu.registers.a, 0x77);
+        assert_eq!(cpu.sp, 0xDFFE);
+        // The return address was pushed high byte first
+        assert_eq!(bus.read_byte(0xDFFD), 0xC0);
+        assert_eq!(bus.read_byte(0xDFFC), 0x03);
+    }
+
+    #[test]
+    fn rotate_through_carry() {
+        // LD A, 0x80; RL A
+        let cpu = run(&[0x3E, 0x80, 0xCB, 0x17]);
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_eq!(flags(&cpu), ZERO | CARRY);
+
+        // LD A, 0x80; RLA never sets Z
+        let cpu = run(&[0x3E, 0x80, 0x17]);
+        assert_eq!(cpu.registers.a, 0x00);
+        assert_eq!(flags(&cpu), CARRY);
+    }
+
+    #[test]
+    fn prefixed_instructions() {
+        // LD A, 0xF0; SWAP A
+        let cpu = run(&[0x3E, 0xF0, 0xCB, 0x37]);
+        assert_eq!(cpu.registers.a, 0x0F);
+        assert_eq!(flags(&cpu), 0);
+
+        // SCF; LD H, 0x80; BIT 7, H keeps C
+        let cpu = run(&[0x37, 0x26, 0x80, 0xCB, 0x7C]);
+        assert_eq!(flags(&cpu), HALF_CARRY | CARRY);
+
+        // LD B, 0x81; SRA B keeps bit 7
+        let cpu = run(&[0x06, 0x81, 0xCB, 0x28]);
+        assert_eq!(cpu.registers.b, 0xC0);
+        assert_eq!(flags(&cpu), CARRY);
 
-        (next_pc, cycles)
+        // LD HL, 0xC100; LD (HL), 0x81; RLC (HL); SET 4, (HL)
+        let (_, bus) = run_until(&[0x21, 0x00, 0xC1, 0x36, 0x81, 0xCB, 0x06, 0xCB, 0xE6], 9);
+        assert_eq!(bus.read_byte(0xC100), 0x13);
     }
 }
@@ -0,0 +1,53 @@
+const TRANSFER_LENGTH: usize = 0xA0;
+
+/// OAM DMA. Writing to 0xFF46 copies 160 bytes from `source << 8` into OAM,
+/// one byte per M-cycle after a cycle of start-up delay.
+pub struct Dma {
+    source: u8,
+    progress: Option<usize>,
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Self {
+            source: 0xFF,
+            progress: None,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        self.source
+    }
+
+    /// Sets the register without starting a transfer.
+    pub fn set_source(&mut self, source: u8) {
+        self.source = source;
+    }
+
+    pub fn start(&mut self, source: u8) {
+        self.source = source;
+        self.progress = Some(0);
+    }
+
+    /// Whether OAM is currently being written by the transfer.
+    pub fn active(&self) -> bool {
+        matches!(self.progress, Some(progress) if progress > 0)
+    }
+
+    /// Advances the transfer by one M-cycle and returns the source address
+    /// and OAM offset of the byte to copy during it, if any.
+    pub fn step(&mut self) -> Option<(u16, usize)> {
+        let progress = self.progress?;
+        if progress == 0 {
+            self.progress = Some(1);
+            return None;
+        }
+        let offset = progress - 1;
+        self.progress = if progress < TRANSFER_LENGTH {
+            Some(progress + 1)
+        } else {
+            None
+        };
+        Some((((self.source as u16) << 8) | offset as u16, offset))
+    }
+}
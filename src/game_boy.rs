@@ -0,0 +1,115 @@
+use crate::cartridge::Cartridge;
+use crate::cpu::CPU;
+use crate::joypad::Button;
+use crate::mbc::ImageSource;
+use crate::memory_bus::MemoryBus;
+use crate::model::Model;
+
+/// Cycles the LCD takes to draw a frame, which is also how often frames are
+/// handed out while the CPU is stopped and nothing draws them.
+const CYCLES_PER_FRAME: usize = 70224;
+
+/// Owns the CPU and everything hanging off the bus. The CPU ticks the bus on
+/// every M-cycle it spends, which keeps all peripherals in lockstep with it.
+pub struct GameBoy {
+    cpu: CPU,
+    bus: MemoryBus,
+    rumble: bool,
+    rumble_callback: Option<Box<dyn FnMut(bool)>>,
+}
+
+impl GameBoy {
+    /// Starts out running the given boot ROM, or with the state the model's
+    /// boot ROM would leave behind for the cartridge if there is none.
+    pub fn new(
+        model: Model,
+        cartridge: Cartridge,
+        boot_rom: Option<Vec<u8>>,
+    ) -> Result<Self, String> {
+        let (cpu, bus) = match boot_rom {
+            Some(boot_rom) => {
+                let mut bus = MemoryBus::power_on(model, cartridge);
+                bus.load_boot_rom(boot_rom)?;
+                (CPU::power_on(), bus)
+            }
+            None => (
+                CPU::new(model, &cartridge.header),
+                MemoryBus::new(model, cartridge),
+            ),
+        };
+        Ok(Self {
+            cpu,
+            bus,
+            rumble: false,
+            rumble_callback: None,
+        })
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        self.bus.cartridge_mut()
+    }
+
+    #[allow(dead_code)]
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        self.bus
+            .joypad
+            .set_button(button, pressed, &mut self.bus.interrupts);
+    }
+
+    /// Tilts cartridges with an accelerometer, in units of g along the
+    /// horizontal and vertical axis.
+    #[allow(dead_code)]
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.bus.set_tilt(x, y);
+    }
+
+    /// Replaces what the Pocket Camera sees, which is a test pattern by
+    /// default. Has no effect on other cartridges.
+    pub fn set_image_source<S: ImageSource + 'static>(&mut self, source: S) {
+        self.bus.set_image_source(Box::new(source));
+    }
+
+    /// Registers a function that gets called whenever the cartridge turns its
+    /// rumble motor on or off.
+    #[allow(dead_code)]
+    pub fn on_rumble<F: FnMut(bool) + 'static>(&mut self, callback: F) {
+        self.rumble_callback = Some(Box::new(callback));
+    }
+
+    /// Runs for at least the given number of cycles and returns how many
+    /// actually elapsed, since instructions can't be interrupted halfway.
+    #[allow(dead_code)]
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<usize, String> {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step()?.0;
+        }
+        Ok(elapsed)
+    }
+
+    /// Runs until the LCD has finished drawing the current frame, or for a
+    /// frame's worth of cycles while stopped, and returns the number of
+    /// cycles that took.
+    pub fn run_frame(&mut self) -> Result<usize, String> {
+        let mut elapsed = 0;
+        loop {
+            let (cycles, frame_done) = self.step()?;
+            elapsed += cycles;
+            if frame_done || (self.cpu.is_stopped() && elapsed >= CYCLES_PER_FRAME) {
+                return Ok(elapsed);
+            }
+        }
+    }
+
+    fn step(&mut self) -> Result<(usize, bool), String> {
+        let cycles = self.cpu.step(&mut self.bus)?;
+        let rumble = self.bus.rumble();
+        if rumble != self.rumble {
+            self.rumble = rumble;
+            if let Some(callback) = &mut self.rumble_callback {
+                callback(rumble);
+            }
+        }
+        Ok((cycles, self.bus.take_frame_done()))
+    }
+}
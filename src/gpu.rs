@@ -1,25 +1,468 @@
-const VIDEO_RAM_SIZE: usize = 0x2000;
-const PIXEL_COUNT: usize = 160 * 144;
+use crate::interrupts::{Interrupt, InterruptController};
+
+/// Two banks of 8 KiB; only the CGB can switch to the second one.
+const VIDEO_RAM_SIZE: usize = 0x4000;
+const SCREEN_WIDTH: usize = 160;
+const PIXEL_COUNT: usize = SCREEN_WIDTH * 144;
 const OAM_SIZE: usize = 0xA0;
 const TILE_SIZE: usize = 64;
 const TILE_SET_SIZE: usize = 384;
+/// Tile data occupies the first 0x1800 bytes of VRAM, 16 bytes per tile.
+const TILE_DATA_SIZE: usize = TILE_SET_SIZE * 16;
+const DOTS_PER_LINE: usize = 456;
+const OAM_SCAN_DOTS: usize = 80;
+const DRAWING_DOTS: usize = 172;
+const VISIBLE_LINES: u8 = 144;
+const LINES_PER_FRAME: u8 = 154;
+const DOTS_PER_FRAME: usize = DOTS_PER_LINE * LINES_PER_FRAME as usize;
+
+const BG_ENABLE: u8 = 1 << 0;
+const BG_TILE_MAP: u8 = 1 << 3;
+const TILE_DATA_UNSIGNED: u8 = 1 << 4;
+const WINDOW_ENABLE: u8 = 1 << 5;
+const WINDOW_TILE_MAP: u8 = 1 << 6;
+const LCD_ENABLE: u8 = 1 << 7;
+const STAT_COINCIDENCE: u8 = 1 << 2;
+const STAT_HBLANK_INTERRUPT: u8 = 1 << 3;
+const STAT_VBLANK_INTERRUPT: u8 = 1 << 4;
+const STAT_OAM_INTERRUPT: u8 = 1 << 5;
+const STAT_COINCIDENCE_INTERRUPT: u8 = 1 << 6;
 
 type Tile = [u8; TILE_SIZE];
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// One of the two CGB palette memories, holding 8 palettes of 4 RGB555
+/// colors. It is accessed through an index register (BCPS/OCPS) and a data
+/// register (BCPD/OCPD).
+pub struct PaletteRam {
+    data: [u8; 64],
+    /// Bits 0-5 select a byte, bit 7 increments the index after writes.
+    index: u8,
+}
+
+impl PaletteRam {
+    fn new() -> Self {
+        Self {
+            data: [0; 64],
+            index: 0,
+        }
+    }
+
+    fn read_index(&self) -> u8 {
+        // Bit 6 is unused
+        0x40 | self.index
+    }
+
+    fn write_index(&mut self, value: u8) {
+        self.index = value & 0xBF;
+    }
+
+    fn read_data(&self) -> u8 {
+        self.data[(self.index & 0x3F) as usize]
+    }
+
+    /// The index increments even when the write itself is blocked.
+    fn write_data(&mut self, value: u8, accessible: bool) {
+        if accessible {
+            self.data[(self.index & 0x3F) as usize] = value;
+        }
+        if self.index & 0x80 != 0 {
+            self.index = 0x80 | (self.index.wrapping_add(1) & 0x3F);
+        }
+    }
+}
+
 pub struct GPU {
     pub ram: [u8; VIDEO_RAM_SIZE],
+    pub vram_bank: u8,
     pub tile_set: [Tile; TILE_SET_SIZE],
+    /// The shade of every pixel on screen, 0 (white) to 3 (black).
     pub canvas_buffer: [u8; PIXEL_COUNT],
     pub oam: [u8; OAM_SIZE],
+    pub lcdc: u8,
+    pub stat: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub lyc: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wy: u8,
+    pub wx: u8,
+    pub mode: Mode,
+    pub bg_palettes: PaletteRam,
+    pub obj_palettes: PaletteRam,
+    line_dots: usize,
+    /// The STAT interrupt is requested when the OR of all enabled sources
+    /// goes high, so one source blocks another while it is active.
+    stat_line: bool,
+    /// Dots since the LCD got turned off, to keep frames coming while it is.
+    off_dots: usize,
+    /// The window keeps its own line counter, which only advances on lines
+    /// where the window was drawn.
+    window_line: u8,
+    /// LY matched WY at the start of a line this frame. The window is only
+    /// drawn from then on, even if WY changes again.
+    window_y_triggered: bool,
 }
 
 impl GPU {
     pub fn new() -> Self {
         Self {
             ram: [0; VIDEO_RAM_SIZE],
+            vram_bank: 0,
             tile_set: [[0; TILE_SIZE]; TILE_SET_SIZE],
             canvas_buffer: [0; PIXEL_COUNT],
             oam: [0; OAM_SIZE],
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::HBlank,
+            bg_palettes: PaletteRam::new(),
+            obj_palettes: PaletteRam::new(),
+            line_dots: 0,
+            stat_line: false,
+            off_dots: 0,
+            window_line: 0,
+            window_y_triggered: false,
+        }
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            0xFF40 => self.lcdc,
+            // Bit 7 is unused
+            0xFF41 => {
+                let coincidence = if self.lcd_enabled() && self.ly == self.lyc {
+                    STAT_COINCIDENCE
+                } else {
+                    0
+                };
+                0x80 | self.stat | coincidence | self.mode as u8
+            }
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly,
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF40 => {
+                if self.lcdc & LCD_ENABLE != 0 && value & LCD_ENABLE == 0 {
+                    // Turning the LCD off resets it to the start of a frame
+                    self.ly = 0;
+                    self.line_dots = 0;
+                    self.mode = Mode::HBlank;
+                    self.off_dots = 0;
+                    self.window_line = 0;
+                    self.window_y_triggered = false;
+                    // Nothing drives the STAT line while the LCD is off
+                    self.stat_line = false;
+                }
+                self.lcdc = value;
+            }
+            // Only the interrupt selection bits are writable
+            0xFF41 => self.stat = (self.stat & 0x07) | (value & 0x78),
+            0xFF42 => self.scy = value,
+            0xFF43 => self.scx = value,
+            // LY is read-only
+            0xFF44 => (),
+            0xFF45 => self.lyc = value,
+            0xFF47 => self.bgp = value,
+            0xFF48 => self.obp0 = value,
+            0xFF49 => self.obp1 = value,
+            0xFF4A => self.wy = value,
+            0xFF4B => self.wx = value,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads BCPS, BCPD, OCPS or OCPD. Palette data can't be accessed while
+    /// the LCD is drawing.
+    pub fn read_palette(&self, address: u16) -> u8 {
+        match address {
+            0xFF68 => self.bg_palettes.read_index(),
+            0xFF69 if self.palettes_accessible() => self.bg_palettes.read_data(),
+            0xFF6A => self.obj_palettes.read_index(),
+            0xFF6B if self.palettes_accessible() => self.obj_palettes.read_data(),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_palette(&mut self, address: u16, value: u8) {
+        let accessible = self.palettes_accessible();
+        match address {
+            0xFF68 => self.bg_palettes.write_index(value),
+            0xFF69 => self.bg_palettes.write_data(value, accessible),
+            0xFF6A => self.obj_palettes.write_index(value),
+            0xFF6B => self.obj_palettes.write_data(value, accessible),
+            _ => unreachable!(),
+        }
+    }
+
+    fn palettes_accessible(&self) -> bool {
+        !self.lcd_enabled() || self.mode != Mode::Drawing
+    }
+
+    /// Writes to VRAM, keeping the decoded tile set in sync. Only bank 0 is
+    /// decoded into it.
+    pub fn write_vram(&mut self, index: usize, value: u8) {
+        self.ram[index] = value;
+        if index >= TILE_DATA_SIZE {
+            return;
+        }
+        // Each row of a tile is two bytes, holding the low and the high bit
+        // of every pixel's color index
+        let row_start = index & !1;
+        let (low, high) = (self.ram[row_start], self.ram[row_start + 1]);
+        let tile = &mut self.tile_set[index / 16];
+        let row = (index % 16) / 2;
+        for x in 0..8 {
+            let bit = 7 - x;
+            tile[row * 8 + x] = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+        }
+    }
+
+    /// Moves the LCD to the start of the given line, as if it had been
+    /// running for a while.
+    pub fn set_line(&mut self, ly: u8) {
+        self.ly = ly;
+        self.line_dots = 0;
+        self.mode = if ly >= VISIBLE_LINES {
+            Mode::VBlank
+        } else {
+            Mode::OamScan
+        };
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        self.lcdc & LCD_ENABLE != 0
+    }
+
+    /// Advances the LCD by the given number of dots and returns whether it
+    /// entered VBlank, i.e. finished drawing a frame. While the LCD is off a
+    /// frame is still reported every frame's worth of dots.
+    pub fn tick(&mut self, dots: usize, interrupts: &mut InterruptController) -> bool {
+        if !self.lcd_enabled() {
+            self.off_dots += dots;
+            if self.off_dots >= DOTS_PER_FRAME {
+                self.off_dots -= DOTS_PER_FRAME;
+                return true;
+            }
+            return false;
+        }
+
+        let mut frame_done = false;
+        for _ in 0..dots {
+            if self.line_dots == 0 && self.ly == self.wy {
+                self.window_y_triggered = true;
+            }
+            self.line_dots += 1;
+            if self.line_dots == DOTS_PER_LINE {
+                self.line_dots = 0;
+                self.ly = (self.ly + 1) % LINES_PER_FRAME;
+                if self.ly == VISIBLE_LINES {
+                    interrupts.request(Interrupt::VBlank);
+                    frame_done = true;
+                } else if self.ly == 0 {
+                    self.window_line = 0;
+                    self.window_y_triggered = false;
+                }
+            }
+            if self.ly < VISIBLE_LINES && self.line_dots == OAM_SCAN_DOTS + DRAWING_DOTS {
+                self.render_line();
+            }
+            self.mode = if self.ly >= VISIBLE_LINES {
+                Mode::VBlank
+            } else if self.line_dots < OAM_SCAN_DOTS {
+                Mode::OamScan
+            } else if self.line_dots < OAM_SCAN_DOTS + DRAWING_DOTS {
+                Mode::Drawing
+            } else {
+                Mode::HBlank
+            };
+            self.update_stat_line(interrupts);
+        }
+        frame_done
+    }
+
+    /// Draws the background and window for the current line.
+    fn render_line(&mut self) {
+        let line = &mut self.canvas_buffer[self.ly as usize * SCREEN_WIDTH..][..SCREEN_WIDTH];
+        // With the background off, the DMG shows white and no window
+        if self.lcdc & BG_ENABLE == 0 {
+            line.fill(0);
+            return;
+        }
+
+        // The window starts at WX - 7, so with WX < 7 its leftmost columns
+        // are cut off, and past the right edge it is not drawn at all
+        let window_start = if self.lcdc & WINDOW_ENABLE != 0
+            && self.window_y_triggered
+            && (self.wx as usize) < SCREEN_WIDTH + 7
+        {
+            Some(self.wx.saturating_sub(7) as usize)
+        } else {
+            None
+        };
+
+        for (x, pixel) in line.iter_mut().enumerate() {
+            let (map, column, row) = match window_start {
+                Some(start) if x >= start => (
+                    self.lcdc & WINDOW_TILE_MAP != 0,
+                    (x + 7 - self.wx as usize) as u8,
+                    self.window_line,
+                ),
+                _ => (
+                    self.lcdc & BG_TILE_MAP != 0,
+                    self.scx.wrapping_add(x as u8),
+                    self.scy.wrapping_add(self.ly),
+                ),
+            };
+            let map_address = if map { 0x1C00 } else { 0x1800 };
+            let tile_number = self.ram[map_address + row as usize / 8 * 32 + column as usize / 8];
+            // 0x8800 addressing uses signed tile numbers relative to 0x9000
+            let tile = if self.lcdc & TILE_DATA_UNSIGNED != 0 {
+                tile_number as usize
+            } else {
+                (256 + tile_number as i8 as isize) as usize
+            };
+            let color = self.tile_set[tile][(row % 8) as usize * 8 + (column % 8) as usize];
+            *pixel = (self.bgp >> (color * 2)) & 0x03;
+        }
+
+        if window_start.is_some() {
+            self.window_line += 1;
         }
     }
+
+    fn update_stat_line(&mut self, interrupts: &mut InterruptController) {
+        let line = match self.mode {
+            Mode::HBlank => self.stat & STAT_HBLANK_INTERRUPT != 0,
+            Mode::VBlank => self.stat & STAT_VBLANK_INTERRUPT != 0,
+            Mode::OamScan => self.stat & STAT_OAM_INTERRUPT != 0,
+            Mode::Drawing => false,
+        } || (self.ly == self.lyc && self.stat & STAT_COINCIDENCE_INTERRUPT != 0);
+        if line && !self.stat_line {
+            interrupts.request(Interrupt::LcdStat);
+        }
+        self.stat_line = line;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT_INTERRUPT: u8 = 1 << 1;
+
+    fn enabled_gpu(lcdc: u8) -> (GPU, InterruptController) {
+        let mut gpu = GPU::new();
+        gpu.write_register(0xFF40, LCD_ENABLE | lcdc);
+        (gpu, InterruptController::new())
+    }
+
+    fn take_stat_interrupt(interrupts: &mut InterruptController) -> bool {
+        let requested = interrupts.read_flag() & STAT_INTERRUPT != 0;
+        interrupts.acknowledge(Interrupt::LcdStat);
+        requested
+    }
+
+    #[test]
+    fn stat_sources_block_each_other() {
+        let (mut gpu, mut interrupts) = enabled_gpu(BG_ENABLE);
+        gpu.write_register(0xFF41, STAT_HBLANK_INTERRUPT | STAT_COINCIDENCE_INTERRUPT);
+        gpu.write_register(0xFF45, 0);
+
+        gpu.tick(1, &mut interrupts);
+        assert!(take_stat_interrupt(&mut interrupts));
+        // LY == LYC keeps the line high through HBlank on line 0
+        gpu.tick(DOTS_PER_LINE - 1, &mut interrupts);
+        assert!(!take_stat_interrupt(&mut interrupts));
+        // On line 1 it drops during OAM scan and rises again for HBlank
+        gpu.tick(DOTS_PER_LINE, &mut interrupts);
+        assert!(take_stat_interrupt(&mut interrupts));
+    }
+
+    #[test]
+    fn lyc_interrupt() {
+        let (mut gpu, mut interrupts) = enabled_gpu(BG_ENABLE);
+        gpu.write_register(0xFF41, STAT_COINCIDENCE_INTERRUPT);
+        gpu.write_register(0xFF45, 5);
+
+        gpu.tick(5 * DOTS_PER_LINE - 1, &mut interrupts);
+        assert!(!take_stat_interrupt(&mut interrupts));
+        gpu.tick(1, &mut interrupts);
+        assert_eq!(gpu.ly, 5);
+        assert!(take_stat_interrupt(&mut interrupts));
+        assert_eq!(
+            gpu.read_register(0xFF41) & STAT_COINCIDENCE,
+            STAT_COINCIDENCE
+        );
+    }
+
+    #[test]
+    fn window_line_counts_drawn_lines() {
+        let (mut gpu, mut interrupts) = enabled_gpu(BG_ENABLE | WINDOW_ENABLE);
+        gpu.write_register(0xFF4B, 7);
+
+        gpu.tick(3 * DOTS_PER_LINE, &mut interrupts);
+        assert_eq!(gpu.window_line, 3);
+        // Lines without the window don't advance it
+        gpu.write_register(0xFF40, LCD_ENABLE | BG_ENABLE);
+        gpu.tick(2 * DOTS_PER_LINE, &mut interrupts);
+        assert_eq!(gpu.window_line, 3);
+        gpu.write_register(0xFF40, LCD_ENABLE | BG_ENABLE | WINDOW_ENABLE);
+        gpu.tick(DOTS_PER_LINE, &mut interrupts);
+        assert_eq!(gpu.window_line, 4);
+
+        // It starts over with the next frame
+        gpu.tick(DOTS_PER_FRAME - 6 * DOTS_PER_LINE, &mut interrupts);
+        assert_eq!(gpu.ly, 0);
+        assert_eq!(gpu.window_line, 0);
+    }
+
+    #[test]
+    fn window_y_latches_once_per_frame() {
+        let (mut gpu, mut interrupts) = enabled_gpu(BG_ENABLE | WINDOW_ENABLE);
+        gpu.write_register(0xFF4A, 30);
+        gpu.write_register(0xFF4B, 7);
+
+        // Moving WY above LY never matches it, so no window this frame
+        gpu.tick(20 * DOTS_PER_LINE, &mut interrupts);
+        gpu.write_register(0xFF4A, 5);
+        gpu.tick(10 * DOTS_PER_LINE, &mut interrupts);
+        assert_eq!(gpu.window_line, 0);
+
+        // Next frame it matches on line 5 and stays on after WY moves again
+        gpu.tick(DOTS_PER_FRAME - 30 * DOTS_PER_LINE, &mut interrupts);
+        gpu.tick(10 * DOTS_PER_LINE, &mut interrupts);
+        gpu.write_register(0xFF4A, 100);
+        gpu.tick(10 * DOTS_PER_LINE, &mut interrupts);
+        assert_eq!(gpu.window_line, 15);
+    }
 }
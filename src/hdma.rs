@@ -0,0 +1,77 @@
+const BLOCK_SIZE: u16 = 0x10;
+
+/// CGB VRAM DMA (HDMA1-HDMA5). Copies blocks of 16 bytes into VRAM, either
+/// all at once (general purpose) or one block per HBlank. The CPU isn't
+/// stalled while blocks are copied.
+pub struct Hdma {
+    source: u16,
+    /// Offset into VRAM.
+    destination: u16,
+    /// Blocks left minus one. Wraps around once the last block is copied,
+    /// which makes HDMA5 read 0xFF.
+    length: u8,
+    active: bool,
+    hblank: bool,
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+        Self {
+            source: 0,
+            destination: 0,
+            length: 0xFF,
+            active: false,
+            hblank: false,
+        }
+    }
+
+    /// Reads HDMA5. Bit 7 is set while no transfer is running.
+    pub fn read(&self) -> u8 {
+        if self.active {
+            self.length
+        } else {
+            0x80 | self.length
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF51 => self.source = (self.source & 0x00FF) | (value as u16) << 8,
+            0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            0xFF53 => self.destination = (self.destination & 0x00FF) | ((value & 0x1F) as u16) << 8,
+            0xFF54 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+            0xFF55 => {
+                // Clearing bit 7 during an HBlank transfer stops it
+                if self.active && self.hblank && value & 0x80 == 0 {
+                    self.active = false;
+                    return;
+                }
+                self.length = value & 0x7F;
+                self.hblank = value & 0x80 != 0;
+                self.active = true;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn general_purpose_pending(&self) -> bool {
+        self.active && !self.hblank
+    }
+
+    pub fn hblank_pending(&self) -> bool {
+        self.active && self.hblank
+    }
+
+    /// Returns the source address and VRAM offset of the next block and
+    /// moves past it.
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, self.destination);
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & 0x1FF0;
+        if self.length == 0 {
+            self.active = false;
+        }
+        self.length = self.length.wrapping_sub(1);
+        block
+    }
+}
@@ -2,22 +2,87 @@ pub enum Instruction {
     Nop,
     Add(ArithmeticTarget),
     AddHl(WordRegister),
+    AddSp,
     Inc(IncDecType),
     Dec(IncDecType),
     Jp(Jump),
     Jr(JumpRelative),
+    Call(Call),
+    Ret(Return),
+    Reti,
+    Rst(u8),
+    Push(StackTarget),
+    Pop(StackTarget),
     Ld(LoadType),
     Di,
+    Ei,
+    Halt,
+    Stop,
     Adc(ArithmeticTarget),
+    Sub(ArithmeticTarget),
+    Sbc(ArithmeticTarget),
+    And(ArithmeticTarget),
     Xor(ArithmeticTarget),
+    Rlca,
+    Rrca,
     Rra,
     Rla,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
     Or(ArithmeticTarget),
     Cp(ArithmeticTarget),
+    Rlc(PrefixTarget),
+    Rrc(PrefixTarget),
+    Rl(PrefixTarget),
+    Rr(PrefixTarget),
+    Sla(PrefixTarget),
+    Sra(PrefixTarget),
+    Swap(PrefixTarget),
+    Srl(PrefixTarget),
+    Bit(u8, PrefixTarget),
+    Res(u8, PrefixTarget),
+    Set(u8, PrefixTarget),
 }
 
 impl Instruction {
-    pub fn from_byte(byte: u8) -> Result<Self, String> {
+    pub fn from_byte(byte: u8, prefixed: bool) -> Result<Self, String> {
+        if prefixed {
+            Ok(Self::from_byte_prefixed(byte))
+        } else {
+            Self::from_byte_not_prefixed(byte)
+        }
+    }
+
+    fn from_byte_prefixed(byte: u8) -> Self {
+        let target = match byte & 0x07 {
+            0x00 => PrefixTarget::B,
+            0x01 => PrefixTarget::C,
+            0x02 => PrefixTarget::D,
+            0x03 => PrefixTarget::E,
+            0x04 => PrefixTarget::H,
+            0x05 => PrefixTarget::L,
+            0x06 => PrefixTarget::HLA,
+            _ => PrefixTarget::A,
+        };
+        let bit = (byte >> 3) & 0x07;
+        match byte >> 3 {
+            0x00 => Instruction::Rlc(target),
+            0x01 => Instruction::Rrc(target),
+            0x02 => Instruction::Rl(target),
+            0x03 => Instruction::Rr(target),
+            0x04 => Instruction::Sla(target),
+            0x05 => Instruction::Sra(target),
+            0x06 => Instruction::Swap(target),
+            0x07 => Instruction::Srl(target),
+            0x08..=0x0F => Instruction::Bit(bit, target),
+            0x10..=0x17 => Instruction::Res(bit, target),
+            _ => Instruction::Set(bit, target),
+        }
+    }
+
+    fn from_byte_not_prefixed(byte: u8) -> Result<Self, String> {
         let ins = match byte {
             0x00 => Instruction::Nop,
             0x01 => Instruction::Ld(LoadType::Word(WordRegister::BC)),
@@ -26,20 +91,23 @@ impl Instruction {
             0x04 => Instruction::Inc(IncDecType::Byte(IncDecByteTarget::B)),
             0x05 => Instruction::Dec(IncDecType::Byte(IncDecByteTarget::B)),
             0x06 => Instruction::Ld(LoadType::Byte(LoadByteTarget::B, LoadByteSource::Immediate)),
+            0x07 => Instruction::Rlca,
+            0x08 => Instruction::Ld(LoadType::AddressFromSp),
             0x09 => Instruction::AddHl(WordRegister::BC),
             0x0A => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::BCA)),
             0x0B => Instruction::Dec(IncDecType::Word(IncDecWordTarget::BC)),
             0x0C => Instruction::Inc(IncDecType::Byte(IncDecByteTarget::C)),
             0x0D => Instruction::Dec(IncDecType::Byte(IncDecByteTarget::C)),
             0x0E => Instruction::Ld(LoadType::Byte(LoadByteTarget::C, LoadByteSource::Immediate)),
-            0x17 => Instruction::Rla,
-            0x1F => Instruction::Rra,
+            0x0F => Instruction::Rrca,
+            0x10 => Instruction::Stop,
             0x11 => Instruction::Ld(LoadType::Word(WordRegister::DE)),
             0x12 => Instruction::Ld(LoadType::Byte(LoadByteTarget::DEA, LoadByteSource::A)),
             0x13 => Instruction::Inc(IncDecType::Word(IncDecWordTarget::DE)),
             0x14 => Instruction::Inc(IncDecType::Byte(IncDecByteTarget::D)),
             0x15 => Instruction::Dec(IncDecType::Byte(IncDecByteTarget::D)),
             0x16 => Instruction::Ld(LoadType::Byte(LoadByteTarget::D, LoadByteSource::Immediate)),
+            0x17 => Instruction::Rla,
             0x18 => Instruction::Jr(JumpRelative::Always),
             0x19 => Instruction::AddHl(WordRegister::DE),
             0x1A => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::DEA)),
@@ -47,6 +115,7 @@ impl Instruction {
             0x1C => Instruction::Inc(IncDecType::Byte(IncDecByteTarget::E)),
             0x1D => Instruction::Dec(IncDecType::Byte(IncDecByteTarget::E)),
             0x1E => Instruction::Ld(LoadType::Byte(LoadByteTarget::E, LoadByteSource::Immediate)),
+            0x1F => Instruction::Rra,
             0x20 => Instruction::Jr(JumpRelative::Conditional(JumpCondition::NotZero)),
             0x21 => Instruction::Ld(LoadType::Word(WordRegister::HL)),
             0x22 => Instruction::Ld(LoadType::Byte(LoadByteTarget::HLIA, LoadByteSource::A)),
@@ -54,6 +123,7 @@ impl Instruction {
             0x24 => Instruction::Inc(IncDecType::Byte(IncDecByteTarget::H)),
             0x25 => Instruction::Dec(IncDecType::Byte(IncDecByteTarget::H)),
             0x26 => Instruction::Ld(LoadType::Byte(LoadByteTarget::H, LoadByteSource::Immediate)),
+            0x27 => Instruction::Daa,
             0x28 => Instruction::Jr(JumpRelative::Conditional(JumpCondition::Zero)),
             0x29 => Instruction::AddHl(WordRegister::HL),
             0x2A => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::HLIA)),
@@ -61,6 +131,7 @@ impl Instruction {
             0x2C => Instruction::Inc(IncDecType::Byte(IncDecByteTarget::L)),
             0x2D => Instruction::Dec(IncDecType::Byte(IncDecByteTarget::L)),
             0x2E => Instruction::Ld(LoadType::Byte(LoadByteTarget::L, LoadByteSource::Immediate)),
+            0x2F => Instruction::Cpl,
             0x30 => Instruction::Jr(JumpRelative::Conditional(JumpCondition::NotCarry)),
             0x31 => Instruction::Ld(LoadType::Word(WordRegister::SP)),
             0x32 => Instruction::Ld(LoadType::Byte(LoadByteTarget::HLDA, LoadByteSource::A)),
@@ -71,6 +142,7 @@ impl Instruction {
                 LoadByteTarget::HLA,
                 LoadByteSource::Immediate,
             )),
+            0x37 => Instruction::Scf,
             0x38 => Instruction::Jr(JumpRelative::Conditional(JumpCondition::Carry)),
             0x39 => Instruction::AddHl(WordRegister::SP),
             0x3A => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::HLDA)),
@@ -78,6 +150,7 @@ impl Instruction {
             0x3C => Instruction::Inc(IncDecType::Byte(IncDecByteTarget::A)),
             0x3D => Instruction::Dec(IncDecType::Byte(IncDecByteTarget::A)),
             0x3E => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::Immediate)),
+            0x3F => Instruction::Ccf,
             0x40 => Instruction::Ld(LoadType::Byte(LoadByteTarget::B, LoadByteSource::B)),
             0x41 => Instruction::Ld(LoadType::Byte(LoadByteTarget::B, LoadByteSource::C)),
             0x42 => Instruction::Ld(LoadType::Byte(LoadByteTarget::B, LoadByteSource::D)),
@@ -132,6 +205,7 @@ impl Instruction {
             0x73 => Instruction::Ld(LoadType::Byte(LoadByteTarget::HLA, LoadByteSource::E)),
             0x74 => Instruction::Ld(LoadType::Byte(LoadByteTarget::HLA, LoadByteSource::H)),
             0x75 => Instruction::Ld(LoadType::Byte(LoadByteTarget::HLA, LoadByteSource::L)),
+            0x76 => Instruction::Halt,
             0x77 => Instruction::Ld(LoadType::Byte(LoadByteTarget::HLA, LoadByteSource::A)),
             0x78 => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::B)),
             0x79 => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::C)),
@@ -157,6 +231,30 @@ impl Instruction {
             0x8D => Instruction::Adc(ArithmeticTarget::L),
             0x8E => Instruction::Adc(ArithmeticTarget::HLA),
             0x8F => Instruction::Adc(ArithmeticTarget::A),
+            0x90 => Instruction::Sub(ArithmeticTarget::B),
+            0x91 => Instruction::Sub(ArithmeticTarget::C),
+            0x92 => Instruction::Sub(ArithmeticTarget::D),
+            0x93 => Instruction::Sub(ArithmeticTarget::E),
+            0x94 => Instruction::Sub(ArithmeticTarget::H),
+            0x95 => Instruction::Sub(ArithmeticTarget::L),
+            0x96 => Instruction::Sub(ArithmeticTarget::HLA),
+            0x97 => Instruction::Sub(ArithmeticTarget::A),
+            0x98 => Instruction::Sbc(ArithmeticTarget::B),
+            0x99 => Instruction::Sbc(ArithmeticTarget::C),
+            0x9A => Instruction::Sbc(ArithmeticTarget::D),
+            0x9B => Instruction::Sbc(ArithmeticTarget::E),
+            0x9C => Instruction::Sbc(ArithmeticTarget::H),
+            0x9D => Instruction::Sbc(ArithmeticTarget::L),
+            0x9E => Instruction::Sbc(ArithmeticTarget::HLA),
+            0x9F => Instruction::Sbc(ArithmeticTarget::A),
+            0xA0 => Instruction::And(ArithmeticTarget::B),
+            0xA1 => Instruction::And(ArithmeticTarget::C),
+            0xA2 => Instruction::And(ArithmeticTarget::D),
+            0xA3 => Instruction::And(ArithmeticTarget::E),
+            0xA4 => Instruction::And(ArithmeticTarget::H),
+            0xA5 => Instruction::And(ArithmeticTarget::L),
+            0xA6 => Instruction::And(ArithmeticTarget::HLA),
+            0xA7 => Instruction::And(ArithmeticTarget::A),
             0xA8 => Instruction::Xor(ArithmeticTarget::B),
             0xA9 => Instruction::Xor(ArithmeticTarget::C),
             0xAA => Instruction::Xor(ArithmeticTarget::D),
@@ -181,39 +279,82 @@ impl Instruction {
             0xBD => Instruction::Cp(ArithmeticTarget::L),
             0xBE => Instruction::Cp(ArithmeticTarget::HLA),
             0xBF => Instruction::Cp(ArithmeticTarget::A),
+            0xC0 => Instruction::Ret(Return::Conditional(JumpCondition::NotZero)),
+            0xC1 => Instruction::Pop(StackTarget::BC),
             0xC2 => Instruction::Jp(Jump::Conditional(JumpCondition::NotZero)),
             0xC3 => Instruction::Jp(Jump::Always(JumpTarget::Immediate)),
+            0xC4 => Instruction::Call(Call::Conditional(JumpCondition::NotZero)),
+            0xC5 => Instruction::Push(StackTarget::BC),
             0xC6 => Instruction::Add(ArithmeticTarget::Immediate),
+            0xC7 => Instruction::Rst(0x00),
+            0xC8 => Instruction::Ret(Return::Conditional(JumpCondition::Zero)),
+            0xC9 => Instruction::Ret(Return::Always),
             0xCA => Instruction::Jp(Jump::Conditional(JumpCondition::Zero)),
+            0xCB => return Err(format!("unexpected prefix byte: {:02X}", byte)),
+            0xCC => Instruction::Call(Call::Conditional(JumpCondition::Zero)),
+            0xCD => Instruction::Call(Call::Always),
             0xCE => Instruction::Adc(ArithmeticTarget::Immediate),
+            0xCF => Instruction::Rst(0x08),
+            0xD0 => Instruction::Ret(Return::Conditional(JumpCondition::NotCarry)),
+            0xD1 => Instruction::Pop(StackTarget::DE),
             0xD2 => Instruction::Jp(Jump::Conditional(JumpCondition::NotCarry)),
             0xD3 => return Err(format!("illegal instruction: {:02X}", byte)),
+            0xD4 => Instruction::Call(Call::Conditional(JumpCondition::NotCarry)),
+            0xD5 => Instruction::Push(StackTarget::DE),
+            0xD6 => Instruction::Sub(ArithmeticTarget::Immediate),
+            0xD7 => Instruction::Rst(0x10),
+            0xD8 => Instruction::Ret(Return::Conditional(JumpCondition::Carry)),
+            0xD9 => Instruction::Reti,
             0xDA => Instruction::Jp(Jump::Conditional(JumpCondition::Carry)),
             0xDB => return Err(format!("illegal instruction: {:02X}", byte)),
-            0xDC => return Err(format!("illegal instruction: {:02X}", byte)),
+            0xDC => Instruction::Call(Call::Conditional(JumpCondition::Carry)),
+            0xDD => return Err(format!("illegal instruction: {:02X}", byte)),
+            0xDE => Instruction::Sbc(ArithmeticTarget::Immediate),
+            0xDF => Instruction::Rst(0x18),
+            0xE0 => Instruction::Ld(LoadType::Byte(
+                LoadByteTarget::ImmediateHighAddress,
+                LoadByteSource::A,
+            )),
+            0xE1 => Instruction::Pop(StackTarget::HL),
+            0xE2 => Instruction::Ld(LoadType::Byte(LoadByteTarget::CA, LoadByteSource::A)),
             0xE3 => return Err(format!("illegal instruction: {:02X}", byte)),
             0xE4 => return Err(format!("illegal instruction: {:02X}", byte)),
+            0xE5 => Instruction::Push(StackTarget::HL),
+            0xE6 => Instruction::And(ArithmeticTarget::Immediate),
+            0xE7 => Instruction::Rst(0x20),
+            0xE8 => Instruction::AddSp,
             0xE9 => Instruction::Jp(Jump::Always(JumpTarget::HLA)),
             0xEA => Instruction::Ld(LoadType::Byte(
                 LoadByteTarget::ImmediateAddress,
                 LoadByteSource::A,
             )),
             0xEB => return Err(format!("illegal instruction: {:02X}", byte)),
-            0xEE => Instruction::Xor(ArithmeticTarget::Immediate),
             0xEC => return Err(format!("illegal instruction: {:02X}", byte)),
             0xED => return Err(format!("illegal instruction: {:02X}", byte)),
+            0xEE => Instruction::Xor(ArithmeticTarget::Immediate),
+            0xEF => Instruction::Rst(0x28),
+            0xF0 => Instruction::Ld(LoadType::Byte(
+                LoadByteTarget::A,
+                LoadByteSource::ImmediateHighAddress,
+            )),
+            0xF1 => Instruction::Pop(StackTarget::AF),
             0xF2 => Instruction::Ld(LoadType::Byte(LoadByteTarget::A, LoadByteSource::CA)),
             0xF3 => Instruction::Di,
             0xF4 => return Err(format!("illegal instruction: {:02X}", byte)),
+            0xF5 => Instruction::Push(StackTarget::AF),
             0xF6 => Instruction::Or(ArithmeticTarget::Immediate),
+            0xF7 => Instruction::Rst(0x30),
+            0xF8 => Instruction::Ld(LoadType::HlFromSpOffset),
+            0xF9 => Instruction::Ld(LoadType::SpFromHl),
             0xFA => Instruction::Ld(LoadType::Byte(
                 LoadByteTarget::A,
                 LoadByteSource::ImmediateAddress,
             )),
+            0xFB => Instruction::Ei,
             0xFC => return Err(format!("illegal instruction: {:02X}", byte)),
             0xFD => return Err(format!("illegal instruction: {:02X}", byte)),
             0xFE => Instruction::Cp(ArithmeticTarget::Immediate),
-            _ => return Err(format!("could not decode instruction: {:#02X}", byte)),
+            0xFF => Instruction::Rst(0x38),
         };
         Ok(ins)
     }
@@ -236,6 +377,17 @@ pub enum ArithmeticTarget {
     Immediate,
 }
 
+pub enum PrefixTarget {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    HLA,
+}
+
 pub enum IncDecByteTarget {
     A,
     B,
@@ -264,6 +416,16 @@ pub enum JumpRelative {
     Always,
 }
 
+pub enum Call {
+    Conditional(JumpCondition),
+    Always,
+}
+
+pub enum Return {
+    Conditional(JumpCondition),
+    Always,
+}
+
 pub enum JumpCondition {
     NotZero,
     NotCarry,
@@ -279,6 +441,9 @@ pub enum JumpTarget {
 pub enum LoadType {
     Byte(LoadByteTarget, LoadByteSource),
     Word(WordRegister),
+    AddressFromSp,
+    SpFromHl,
+    HlFromSpOffset,
 }
 
 pub enum LoadByteSource {
@@ -291,6 +456,7 @@ pub enum LoadByteSource {
     L,
     Immediate,
     ImmediateAddress,
+    ImmediateHighAddress,
     CA,
     BCA,
     DEA,
@@ -308,6 +474,8 @@ pub enum LoadByteTarget {
     H,
     L,
     ImmediateAddress,
+    ImmediateHighAddress,
+    CA,
     BCA,
     DEA,
     HLA,
@@ -322,24 +490,64 @@ pub enum WordRegister {
     SP,
 }
 
+pub enum StackTarget {
+    AF,
+    BC,
+    DE,
+    HL,
+}
+
 impl std::fmt::Debug for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Instruction::Nop => f.write_str("NOP"),
             Instruction::Add(target) => f.write_fmt(format_args!("ADD A, {:?}", target)),
             Instruction::AddHl(target) => f.write_fmt(format_args!("ADD HL, {:?}", target)),
+            Instruction::AddSp => f.write_str("ADD SP, r8"),
             Instruction::Inc(inc_dec_type) => f.write_fmt(format_args!("INC {:?}", inc_dec_type)),
             Instruction::Dec(inc_dec_type) => f.write_fmt(format_args!("DEC {:?}", inc_dec_type)),
+            Instruction::Rlca => f.write_str("RLCA"),
+            Instruction::Rrca => f.write_str("RRCA"),
             Instruction::Rra => f.write_str("RRA"),
             Instruction::Rla => f.write_str("RLA"),
+            Instruction::Daa => f.write_str("DAA"),
+            Instruction::Cpl => f.write_str("CPL"),
+            Instruction::Scf => f.write_str("SCF"),
+            Instruction::Ccf => f.write_str("CCF"),
             Instruction::Jp(jump) => f.write_fmt(format_args!("JP {:?}", jump)),
             Instruction::Jr(jump) => f.write_fmt(format_args!("JR {:?}", jump)),
+            Instruction::Call(call) => f.write_fmt(format_args!("CALL {:?}", call)),
+            Instruction::Ret(Return::Conditional(condition)) => {
+                f.write_fmt(format_args!("RET {:?}", condition))
+            }
+            Instruction::Ret(Return::Always) => f.write_str("RET"),
+            Instruction::Reti => f.write_str("RETI"),
+            Instruction::Rst(vector) => f.write_fmt(format_args!("RST {:02X}H", vector)),
+            Instruction::Push(target) => f.write_fmt(format_args!("PUSH {:?}", target)),
+            Instruction::Pop(target) => f.write_fmt(format_args!("POP {:?}", target)),
             Instruction::Ld(load_type) => f.write_fmt(format_args!("LD {:?}", load_type)),
             Instruction::Di => f.write_str("DI"),
+            Instruction::Ei => f.write_str("EI"),
+            Instruction::Halt => f.write_str("HALT"),
+            Instruction::Stop => f.write_str("STOP"),
             Instruction::Adc(target) => f.write_fmt(format_args!("ADC A, {:?}", target)),
+            Instruction::Sub(target) => f.write_fmt(format_args!("SUB {:?}", target)),
+            Instruction::Sbc(target) => f.write_fmt(format_args!("SBC A, {:?}", target)),
+            Instruction::And(target) => f.write_fmt(format_args!("AND {:?}", target)),
             Instruction::Xor(target) => f.write_fmt(format_args!("XOR {:?}", target)),
             Instruction::Or(target) => f.write_fmt(format_args!("OR {:?}", target)),
             Instruction::Cp(target) => f.write_fmt(format_args!("CP {:?}", target)),
+            Instruction::Rlc(target) => f.write_fmt(format_args!("RLC {:?}", target)),
+            Instruction::Rrc(target) => f.write_fmt(format_args!("RRC {:?}", target)),
+            Instruction::Rl(target) => f.write_fmt(format_args!("RL {:?}", target)),
+            Instruction::Rr(target) => f.write_fmt(format_args!("RR {:?}", target)),
+            Instruction::Sla(target) => f.write_fmt(format_args!("SLA {:?}", target)),
+            Instruction::Sra(target) => f.write_fmt(format_args!("SRA {:?}", target)),
+            Instruction::Swap(target) => f.write_fmt(format_args!("SWAP {:?}", target)),
+            Instruction::Srl(target) => f.write_fmt(format_args!("SRL {:?}", target)),
+            Instruction::Bit(bit, target) => f.write_fmt(format_args!("BIT {}, {:?}", bit, target)),
+            Instruction::Res(bit, target) => f.write_fmt(format_args!("RES {}, {:?}", bit, target)),
+            Instruction::Set(bit, target) => f.write_fmt(format_args!("SET {}, {:?}", bit, target)),
         }
     }
 }
@@ -355,7 +563,23 @@ impl std::fmt::Debug for ArithmeticTarget {
             ArithmeticTarget::H => "H",
             ArithmeticTarget::L => "L",
             ArithmeticTarget::HLA => "(HL)",
-            ArithmeticTarget::Immediate => "a8",
+            ArithmeticTarget::Immediate => "d8",
+        };
+        f.write_str(value)
+    }
+}
+
+impl std::fmt::Debug for PrefixTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            PrefixTarget::A => "A",
+            PrefixTarget::B => "B",
+            PrefixTarget::C => "C",
+            PrefixTarget::D => "D",
+            PrefixTarget::E => "E",
+            PrefixTarget::H => "H",
+            PrefixTarget::L => "L",
+            PrefixTarget::HLA => "(HL)",
         };
         f.write_str(value)
     }
@@ -384,7 +608,7 @@ impl std::fmt::Debug for IncDecType {
 impl std::fmt::Debug for Jump {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Jump::Conditional(condition) => f.write_fmt(format_args!("{:?}", condition)),
+            Jump::Conditional(condition) => f.write_fmt(format_args!("{:?}, a16", condition)),
             Jump::Always(target) => f.write_fmt(format_args!("{:?}", target)),
         }
     }
@@ -393,19 +617,30 @@ impl std::fmt::Debug for Jump {
 impl std::fmt::Debug for JumpRelative {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            JumpRelative::Conditional(condition) => f.write_fmt(format_args!("{:?}", condition)),
+            JumpRelative::Conditional(condition) => {
+                f.write_fmt(format_args!("{:?}, r8", condition))
+            }
             JumpRelative::Always => f.write_str("r8"),
         }
     }
 }
 
+impl std::fmt::Debug for Call {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Call::Conditional(condition) => f.write_fmt(format_args!("{:?}, a16", condition)),
+            Call::Always => f.write_str("a16"),
+        }
+    }
+}
+
 impl std::fmt::Debug for JumpCondition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            JumpCondition::NotZero => f.write_str("NZ, a16"),
-            JumpCondition::NotCarry => f.write_str("NC, a16"),
-            JumpCondition::Zero => f.write_str("Z, a16"),
-            JumpCondition::Carry => f.write_str("C, a16"),
+            JumpCondition::NotZero => f.write_str("NZ"),
+            JumpCondition::NotCarry => f.write_str("NC"),
+            JumpCondition::Zero => f.write_str("Z"),
+            JumpCondition::Carry => f.write_str("C"),
         }
     }
 }
@@ -426,6 +661,9 @@ impl std::fmt::Debug for LoadType {
                 f.write_fmt(format_args!("{:?}, {:?}", target, source))
             }
             LoadType::Word(source) => f.write_fmt(format_args!("{:?}, d16", source)),
+            LoadType::AddressFromSp => f.write_str("(a16), SP"),
+            LoadType::SpFromHl => f.write_str("SP, HL"),
+            LoadType::HlFromSpOffset => f.write_str("HL, SP+r8"),
         }
     }
 }
@@ -442,6 +680,7 @@ impl std::fmt::Debug for LoadByteSource {
             LoadByteSource::L => "L",
             LoadByteSource::Immediate => "d8",
             LoadByteSource::ImmediateAddress => "(a16)",
+            LoadByteSource::ImmediateHighAddress => "(a8)",
             LoadByteSource::CA => "(C)",
             LoadByteSource::BCA => "(BC)",
             LoadByteSource::DEA => "(DE)",
@@ -464,6 +703,8 @@ impl std::fmt::Debug for LoadByteTarget {
             LoadByteTarget::H => "H",
             LoadByteTarget::L => "L",
             LoadByteTarget::ImmediateAddress => "(a16)",
+            LoadByteTarget::ImmediateHighAddress => "(a8)",
+            LoadByteTarget::CA => "(C)",
             LoadByteTarget::BCA => "(BC)",
             LoadByteTarget::DEA => "(DE)",
             LoadByteTarget::HLA => "(HL)",
@@ -485,3 +726,15 @@ impl std::fmt::Debug for WordRegister {
         f.write_str(value)
     }
 }
+
+impl std::fmt::Debug for StackTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            StackTarget::AF => "AF",
+            StackTarget::BC => "BC",
+            StackTarget::DE => "DE",
+            StackTarget::HL => "HL",
+        };
+        f.write_str(value)
+    }
+}
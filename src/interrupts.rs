@@ -0,0 +1,88 @@
+const INTERRUPT_MASK: u8 = 0x1F;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    /// All interrupts in the order the CPU services them.
+    const PRIORITY: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::LcdStat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    fn bit(self) -> u8 {
+        match self {
+            Interrupt::VBlank => 1 << 0,
+            Interrupt::LcdStat => 1 << 1,
+            Interrupt::Timer => 1 << 2,
+            Interrupt::Serial => 1 << 3,
+            Interrupt::Joypad => 1 << 4,
+        }
+    }
+
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::LcdStat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60,
+        }
+    }
+}
+
+/// Holds the IE (0xFFFF) and IF (0xFF0F) registers. Peripherals raise
+/// interrupts through `request`, the CPU picks them up through `next`.
+pub struct InterruptController {
+    enable: u8,
+    flag: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> Self {
+        Self { enable: 0, flag: 0 }
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flag |= interrupt.bit();
+    }
+
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flag &= !interrupt.bit();
+    }
+
+    /// Returns the highest priority interrupt that is both enabled and requested.
+    pub fn next(&self) -> Option<Interrupt> {
+        let pending = self.enable & self.flag & INTERRUPT_MASK;
+        Interrupt::PRIORITY
+            .iter()
+            .copied()
+            .find(|interrupt| pending & interrupt.bit() != 0)
+    }
+
+    pub fn read_enable(&self) -> u8 {
+        self.enable
+    }
+
+    pub fn write_enable(&mut self, value: u8) {
+        self.enable = value;
+    }
+
+    pub fn read_flag(&self) -> u8 {
+        // The upper three bits are unused and always read as 1
+        self.flag | !INTERRUPT_MASK
+    }
+
+    pub fn write_flag(&mut self, value: u8) {
+        self.flag = value & INTERRUPT_MASK;
+    }
+}
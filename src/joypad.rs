@@ -0,0 +1,97 @@
+use crate::interrupts::{Interrupt, InterruptController};
+
+const SELECT_DIRECTIONS: u8 = 1 << 4;
+const SELECT_ACTIONS: u8 = 1 << 5;
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+/// The P1 register. Buttons are active low: a pressed button pulls its line
+/// to 0 in whichever of the two groups is selected.
+pub struct Joypad {
+    select: u8,
+    directions: u8,
+    actions: u8,
+    /// Which controller a Super Game Boy in multiplayer mode reports when
+    /// neither group is selected.
+    pub player: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            select: SELECT_DIRECTIONS | SELECT_ACTIONS,
+            directions: 0x0F,
+            actions: 0x0F,
+            player: 0,
+        }
+    }
+
+    fn lines(&self) -> u8 {
+        if self.select == SELECT_DIRECTIONS | SELECT_ACTIONS {
+            return 0x0F - self.player;
+        }
+        let mut lines = 0x0F;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            lines &= self.directions;
+        }
+        if self.select & SELECT_ACTIONS == 0 {
+            lines &= self.actions;
+        }
+        lines
+    }
+
+    /// Whether a pressed button pulls one of the selected lines low, which is
+    /// what brings the CPU out of STOP.
+    pub fn selected_pressed(&self) -> bool {
+        (self.select & SELECT_DIRECTIONS == 0 && self.directions != 0x0F)
+            || (self.select & SELECT_ACTIONS == 0 && self.actions != 0x0F)
+    }
+
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | self.lines()
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.select = value & (SELECT_DIRECTIONS | SELECT_ACTIONS);
+    }
+
+    /// Updates a button and requests the joypad interrupt if that pulled one
+    /// of the selected lines low.
+    pub fn set_button(
+        &mut self,
+        button: Button,
+        pressed: bool,
+        interrupts: &mut InterruptController,
+    ) {
+        let before = self.lines();
+        let (group, bit) = match button {
+            Button::Right => (&mut self.directions, 0),
+            Button::Left => (&mut self.directions, 1),
+            Button::Up => (&mut self.directions, 2),
+            Button::Down => (&mut self.directions, 3),
+            Button::A => (&mut self.actions, 0),
+            Button::B => (&mut self.actions, 1),
+            Button::Select => (&mut self.actions, 2),
+            Button::Start => (&mut self.actions, 3),
+        };
+        if pressed {
+            *group &= !(1 << bit);
+        } else {
+            *group |= 1 << bit;
+        }
+        if before & !self.lines() != 0 {
+            interrupts.request(Interrupt::Joypad);
+        }
+    }
+}
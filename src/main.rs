@@ -1,28 +1,139 @@
+#![allow(clippy::upper_case_acronyms)]
+
 #[macro_use]
 extern crate log;
 
-use pretty_env_logger;
-
+mod apu;
+mod cartridge;
 mod cpu;
+mod dma;
+mod game_boy;
 mod gpu;
+mod hdma;
 mod instruction;
+mod interrupts;
+mod joypad;
+mod mbc;
 mod memory_bus;
+mod model;
+mod save;
+mod serial;
+mod sgb;
+mod timer;
 
 use std::env;
-use std::fs::File;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use cpu::CPU;
+use cartridge::Cartridge;
+use game_boy::GameBoy;
+use mbc::PgmImage;
+use model::Model;
+use save::SaveFile;
 
 fn main() {
     pretty_env_logger::init();
-    let mut cpu = CPU::new();
-    let rom_path = env::args().nth(1).expect("No rom specified");
-    let mut file = File::open(rom_path).expect("Could not open rom file");
-    cpu.load(&mut file);
-    loop {
-        if let Err(msg) = cpu.step() {
+    let mut rom_path = None;
+    let mut boot_rom_path = None;
+    let mut model = Model::Dmg;
+    let mut camera_image = None;
+    let mut save_dir = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--model" => {
+                let name = args.next().expect("No model specified");
+                model = match name.parse() {
+                    Ok(model) => model,
+                    Err(err) => {
+                        error!("{}", err);
+                        return;
+                    }
+                }
+            }
+            "--boot-rom" => boot_rom_path = Some(args.next().expect("No boot rom specified")),
+            "--camera-image" => {
+                camera_image = Some(args.next().expect("No camera image specified"))
+            }
+            "--save-dir" => {
+                save_dir = Some(PathBuf::from(
+                    args.next().expect("No save directory specified"),
+                ))
+            }
+            _ => rom_path = Some(PathBuf::from(arg)),
+        }
+    }
+    let rom_path = rom_path.expect("No rom specified");
+    let boot_rom = boot_rom_path.map(|path| fs::read(path).expect("Could not read boot rom file"));
+    let rom = fs::read(&rom_path).expect("Could not read rom file");
+    let mut cartridge = match Cartridge::new(rom) {
+        Ok(cartridge) => cartridge,
+        Err(err) => {
+            error!("{}", err);
+            return;
+        }
+    };
+    info!(
+        "{} (type {:02X}, {} KiB ROM, {} KiB RAM)",
+        cartridge.header.title,
+        cartridge.header.cartridge_type,
+        cartridge.header.rom_size / 1024,
+        cartridge.header.ram_size / 1024
+    );
+    let mut save_file = if cartridge.has_battery() {
+        if let Some(dir) = &save_dir {
+            if let Err(err) = fs::create_dir_all(dir) {
+                error!("Could not create save directory {}: {}", dir.display(), err);
+                return;
+            }
+        }
+        let save_file = SaveFile::new(&rom_path, save_dir.as_deref());
+        if let Err(err) = save_file.load(&mut cartridge) {
+            error!("Could not load save file: {}", err);
+            return;
+        }
+        Some(save_file)
+    } else {
+        None
+    };
+    let mut game_boy = match GameBoy::new(model, cartridge, boot_rom) {
+        Ok(game_boy) => game_boy,
+        Err(err) => {
+            error!("{}", err);
+            return;
+        }
+    };
+    if let Some(path) = camera_image {
+        match PgmImage::open(path) {
+            Ok(image) => game_boy.set_image_source(image),
+            Err(err) => {
+                error!("{}", err);
+                return;
+            }
+        }
+    }
+    // Stop at the next frame on Ctrl-C or SIGTERM, so the save gets written
+    let quit = Arc::new(AtomicBool::new(false));
+    let handler_quit = quit.clone();
+    if let Err(err) = ctrlc::set_handler(move || handler_quit.store(true, Ordering::SeqCst)) {
+        warn!("Could not install the quit handler: {}", err);
+    }
+    while !quit.load(Ordering::SeqCst) {
+        if let Err(msg) = game_boy.run_frame() {
             error!("{}", msg);
             break;
         }
+        if let Some(save_file) = &mut save_file {
+            if let Err(err) = save_file.update(game_boy.cartridge_mut()) {
+                error!("Could not write save file: {}", err);
+            }
+        }
+    }
+    if let Some(save_file) = &mut save_file {
+        if let Err(err) = save_file.flush(game_boy.cartridge_mut()) {
+            error!("Could not write save file: {}", err);
+        }
     }
 }
@@ -0,0 +1,121 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod camera;
+mod huc1;
+mod huc3;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc7;
+
+pub use camera::{Camera, ImageSource, PgmImage};
+pub use huc1::HuC1;
+pub use huc3::HuC3;
+pub use mbc1::Mbc1;
+pub use mbc2::{Mbc2, RAM_SIZE as MBC2_RAM_SIZE};
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use mbc7::{Mbc7, EEPROM_SIZE as MBC7_EEPROM_SIZE};
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// A memory bank controller. It decides which parts of the cartridge ROM and
+/// RAM are visible at 0x0000-0x7FFF and 0xA000-0xBFFF, and is configured by
+/// writes to the otherwise read-only ROM area.
+pub trait Mbc {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8;
+    fn write_rom(&mut self, address: u16, value: u8);
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8;
+    /// Returns whether the write changed anything that gets saved, so that
+    /// register writes and writes to disabled RAM don't trigger a save.
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool;
+
+    /// Whether the game currently has RAM enabled. Games disable it after
+    /// they are done writing, which makes for a good time to save it.
+    fn ram_enabled(&self) -> bool {
+        true
+    }
+
+    /// State besides the RAM that has to survive across sessions, such as a
+    /// clock. It is stored after the RAM contents in the save file.
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_state(&mut self, _data: &[u8]) {}
+
+    /// Whether the cartridge's rumble motor is currently on.
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// Feeds the cartridge's accelerometer, in units of g.
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /// Replaces what the cartridge's camera sensor sees.
+    fn set_image_source(&mut self, _source: Box<dyn ImageSource>) {}
+
+    /// Advances hardware on the cartridge that runs on the system clock and
+    /// returns whether it wrote anything that gets saved.
+    fn tick(&mut self, _ram: &mut [u8], _cycles: usize) -> bool {
+        false
+    }
+}
+
+/// Cartridges with at most 32 KiB of ROM and 8 KiB of RAM, mapped directly.
+pub struct RomOnly;
+
+impl Mbc for RomOnly {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        rom_byte(rom, (address as usize) / ROM_BANK_SIZE, address)
+    }
+
+    fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match ram_index(ram, 0, address) {
+            Some(index) => ram[index],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        store(ram, 0, address, value)
+    }
+}
+
+/// Current host time in seconds, which cartridge clocks follow.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Reads from the given 16 KiB ROM bank. Bank numbers wrap around the size
+/// of the ROM, like the unconnected upper address lines do on hardware.
+fn rom_byte(rom: &[u8], bank: usize, address: u16) -> u8 {
+    let offset = bank * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+    rom[offset % rom.len()]
+}
+
+/// Index of the given address within an 8 KiB RAM bank, wrapping around the
+/// size of the RAM. None if the cartridge has no RAM at all.
+fn ram_index(ram: &[u8], bank: usize, address: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+    let offset = bank * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1));
+    Some(offset % ram.len())
+}
+
+/// Writes to the given 8 KiB RAM bank and returns whether that changed the
+/// stored value.
+fn store(ram: &mut [u8], bank: usize, address: u16, value: u8) -> bool {
+    match ram_index(ram, bank, address) {
+        Some(index) => std::mem::replace(&mut ram[index], value) != value,
+        None => false,
+    }
+}
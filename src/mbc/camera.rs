@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::Path;
+
+use super::{ram_index, rom_byte, store, Mbc};
+
+pub const IMAGE_WIDTH: usize = 128;
+pub const IMAGE_HEIGHT: usize = 112;
+/// Where captured images end up in RAM bank 0, as 16x14 tiles.
+const IMAGE_ADDRESS: usize = 0x100;
+const REGISTERS_BANK: u8 = 0x10;
+const CAPTURE: u8 = 1 << 0;
+/// Largest PGM image accepted, in either dimension.
+const MAX_IMAGE_SIZE: usize = 0x4000;
+/// Exposure at which the sensor reports its input unchanged.
+const EXPOSURE_REFERENCE: u32 = 0x1000;
+
+/// Something the camera sensor can look at. Returns one 8-bit grey value per
+/// pixel, row by row, 0 being black.
+pub trait ImageSource {
+    fn capture(&mut self) -> [u8; IMAGE_WIDTH * IMAGE_HEIGHT];
+}
+
+/// A diagonal gradient that shifts with every capture, so a running capture
+/// loop is visible even without an image file.
+pub struct TestPattern {
+    frame: usize,
+}
+
+impl TestPattern {
+    pub fn new() -> Self {
+        Self { frame: 0 }
+    }
+}
+
+impl ImageSource for TestPattern {
+    fn capture(&mut self) -> [u8; IMAGE_WIDTH * IMAGE_HEIGHT] {
+        let mut image = [0; IMAGE_WIDTH * IMAGE_HEIGHT];
+        for (i, pixel) in image.iter_mut().enumerate() {
+            let (x, y) = (i % IMAGE_WIDTH, i / IMAGE_WIDTH);
+            *pixel = ((x + y + self.frame) * 2) as u8;
+        }
+        self.frame += 1;
+        image
+    }
+}
+
+/// A still greyscale image, read from a binary (P5) or plain (P2) PGM file
+/// and scaled to the sensor size.
+pub struct PgmImage {
+    image: [u8; IMAGE_WIDTH * IMAGE_HEIGHT],
+}
+
+impl PgmImage {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|err| format!("could not read image: {}", err))?;
+        Self::parse(&data)
+    }
+
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        // The header is four whitespace separated fields, comments start with '#'
+        let mut fields = Vec::new();
+        let mut position = 0;
+        while fields.len() < 4 {
+            match data.get(position) {
+                Some(b'#') => {
+                    while data.get(position).is_some_and(|byte| *byte != b'\n') {
+                        position += 1;
+                    }
+                }
+                Some(byte) if byte.is_ascii_whitespace() => position += 1,
+                Some(_) => {
+                    let start = position;
+                    while data
+                        .get(position)
+                        .is_some_and(|byte| !byte.is_ascii_whitespace())
+                    {
+                        position += 1;
+                    }
+                    fields.push(String::from_utf8_lossy(&data[start..position]).into_owned());
+                }
+                None => return Err("truncated PGM header".to_string()),
+            }
+        }
+        let number = |field: &str| {
+            field
+                .parse::<usize>()
+                .map_err(|_| format!("invalid PGM header field: {}", field))
+        };
+        let (width, height, max) = (
+            number(&fields[1])?,
+            number(&fields[2])?,
+            number(&fields[3])?,
+        );
+        if width == 0
+            || height == 0
+            || width > MAX_IMAGE_SIZE
+            || height > MAX_IMAGE_SIZE
+            || max == 0
+            || max > 0xFF
+        {
+            return Err(format!(
+                "unsupported PGM image: {}x{}, max {}",
+                width, height, max
+            ));
+        }
+
+        let pixels: Vec<usize> = match fields[0].as_str() {
+            // A single whitespace character separates the header from the data
+            "P5" => data
+                .get(position + 1..)
+                .unwrap_or_default()
+                .iter()
+                .map(|byte| *byte as usize)
+                .collect(),
+            "P2" => String::from_utf8_lossy(&data[position..])
+                .split_ascii_whitespace()
+                .map(number)
+                .collect::<Result<_, _>>()?,
+            magic => return Err(format!("not a PGM image: {}", magic)),
+        };
+        let pixel_count = width
+            .checked_mul(height)
+            .ok_or_else(|| "PGM image too large".to_string())?;
+        if pixels.len() < pixel_count {
+            return Err("truncated PGM image".to_string());
+        }
+
+        let mut image = [0; IMAGE_WIDTH * IMAGE_HEIGHT];
+        for (i, pixel) in image.iter_mut().enumerate() {
+            let x = i % IMAGE_WIDTH * width / IMAGE_WIDTH;
+            let y = i / IMAGE_WIDTH * height / IMAGE_HEIGHT;
+            // Plain PGM samples can exceed the declared maximum
+            *pixel = (pixels[y * width + x].min(max) * 0xFF / max) as u8;
+        }
+        Ok(Self { image })
+    }
+}
+
+impl ImageSource for PgmImage {
+    fn capture(&mut self) -> [u8; IMAGE_WIDTH * IMAGE_HEIGHT] {
+        self.image
+    }
+}
+
+/// The Pocket Camera's mapper. Selecting RAM bank 0x10 maps the sensor's
+/// registers to 0xA000 instead of RAM. A capture scales the source image by
+/// the exposure time and dithers it to four shades using the 4x4 threshold
+/// matrix in registers 0x06-0x35. The sensor's edge enhancement and gain
+/// aren't emulated.
+pub struct Camera {
+    ram_write_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; 0x36],
+    /// Cycles until the capture in progress completes.
+    capture_cycles: Option<usize>,
+    source: Box<dyn ImageSource>,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            ram_write_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0; 0x36],
+            capture_cycles: None,
+            source: Box::new(TestPattern::new()),
+        }
+    }
+
+    fn exposure(&self) -> u32 {
+        (self.registers[2] as u32) << 8 | self.registers[3] as u32
+    }
+
+    fn start_capture(&mut self) {
+        let n = self.registers[1] & 0x80 != 0;
+        let cycles = 32446 + if n { 0 } else { 512 } + 16 * self.exposure() as usize;
+        self.capture_cycles = Some(cycles * 4);
+    }
+
+    fn finish_capture(&mut self, ram: &mut [u8]) {
+        let image = self.source.capture();
+        let exposure = self.exposure();
+        for (i, pixel) in image.iter().enumerate() {
+            let (x, y) = (i % IMAGE_WIDTH, i / IMAGE_WIDTH);
+            let value = (*pixel as u32 * exposure / EXPOSURE_REFERENCE).min(0xFF) as u8;
+            let thresholds = 6 + ((y % 4) * 4 + x % 4) * 3;
+            // Darker than a threshold means a darker shade; 3 is black
+            let shade = self.registers[thresholds..thresholds + 3]
+                .iter()
+                .filter(|threshold| value < **threshold)
+                .count() as u8;
+
+            let tile = (y / 8) * (IMAGE_WIDTH / 8) + x / 8;
+            let row = IMAGE_ADDRESS + tile * 16 + (y % 8) * 2;
+            let bit = 7 - (x % 8);
+            if row + 1 < ram.len() {
+                ram[row] = (ram[row] & !(1 << bit)) | (shade & 1) << bit;
+                ram[row + 1] = (ram[row + 1] & !(1 << bit)) | (shade >> 1) << bit;
+            }
+        }
+    }
+}
+
+impl Mbc for Camera {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_write_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = value & 0x1F,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if self.ram_bank & REGISTERS_BANK != 0 {
+            // Only the capture register can be read back
+            return match address & 0x7F {
+                0x00 => (self.registers[0] & !CAPTURE) | self.capture_cycles.is_some() as u8,
+                _ => 0x00,
+            };
+        }
+        match ram_index(ram, self.ram_bank as usize, address) {
+            Some(index) => ram[index],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        if self.ram_bank & REGISTERS_BANK != 0 {
+            let register = (address & 0x7F) as usize;
+            if register < self.registers.len() {
+                self.registers[register] = value;
+            }
+            if register == 0 && value & CAPTURE != 0 && self.capture_cycles.is_none() {
+                self.start_capture();
+            }
+            return false;
+        }
+        self.ram_write_enabled && store(ram, self.ram_bank as usize, address, value)
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_write_enabled
+    }
+
+    fn tick(&mut self, ram: &mut [u8], cycles: usize) -> bool {
+        match self.capture_cycles {
+            Some(remaining) if remaining > cycles => {
+                self.capture_cycles = Some(remaining - cycles);
+                false
+            }
+            Some(_) => {
+                self.capture_cycles = None;
+                self.registers[0] &= !CAPTURE;
+                // The picture lands in RAM, which is battery backed
+                self.finish_capture(ram);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.source = source;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cartridge::Cartridge;
+
+    #[test]
+    fn finished_capture_marks_ram_dirty() {
+        let mut cartridge = Cartridge::blank(0xFC);
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x10);
+
+        // Starting a capture only writes a register
+        cartridge.write_ram(0xA000, 0x01);
+        assert!(!cartridge.take_dirty());
+        cartridge.tick(4);
+        assert!(!cartridge.take_dirty());
+
+        cartridge.tick(0x100000);
+        assert_eq!(cartridge.read_ram(0xA000) & 0x01, 0);
+        assert!(cartridge.take_dirty());
+    }
+}
@@ -0,0 +1,58 @@
+use super::{ram_index, rom_byte, store, Mbc};
+
+const IR_MODE: u8 = 0x0E;
+/// What the IR receiver reads as when no light is seen.
+const IR_NO_LIGHT: u8 = 0xC0;
+
+/// Hudson's MBC1 lookalike with a 6-bit ROM bank and an infrared port. The
+/// register at 0x0000 switches 0xA000-0xBFFF between RAM and the IR port
+/// instead of enabling RAM.
+pub struct HuC1 {
+    ir_mode: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl HuC1 {
+    pub fn new() -> Self {
+        Self {
+            ir_mode: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+}
+
+impl Mbc for HuC1 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = value & 0x0F == IR_MODE,
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x3F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if self.ir_mode {
+            return IR_NO_LIGHT;
+        }
+        match ram_index(ram, self.ram_bank as usize, address) {
+            Some(index) => ram[index],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        // Nothing is listening on the other end of the IR LED
+        !self.ir_mode && store(ram, self.ram_bank as usize, address, value)
+    }
+}
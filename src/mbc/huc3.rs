@@ -0,0 +1,219 @@
+use std::convert::TryInto;
+
+use super::{now, ram_index, rom_byte, store, Mbc};
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+/// Size of the clock state appended to the save file: minutes and days as
+/// 32-bit values, followed by a 64-bit UNIX timestamp.
+const RTC_SAVE_SIZE: usize = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    RamReadOnly,
+    RamReadWrite,
+    Command,
+    Response,
+    Semaphore,
+    Infrared,
+    Unmapped,
+}
+
+/// Hudson's mapper with a clock that is driven through a small command
+/// interface. Commands are written in one mode, executed through the
+/// semaphore in another, and their results are read back in a third.
+///
+/// The clock counts minutes of the day and days, following the host's wall
+/// clock, and is copied to and from the first nibbles of the RTC memory.
+pub struct HuC3 {
+    mode: Mode,
+    rom_bank: u8,
+    ram_bank: u8,
+    command: u8,
+    response: u8,
+    /// 256 nibbles of memory behind the command interface.
+    memory: [u8; 0x100],
+    address: u8,
+    minutes: u64,
+    days: u64,
+    /// Host time the clock was last brought up to date.
+    updated_at: u64,
+}
+
+impl HuC3 {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::RamReadOnly,
+            rom_bank: 1,
+            ram_bank: 0,
+            command: 0,
+            response: 0,
+            memory: [0; 0x100],
+            address: 0,
+            minutes: 0,
+            days: 0,
+            updated_at: now(),
+        }
+    }
+
+    fn update(&mut self) {
+        let elapsed = now().saturating_sub(self.updated_at) / 60;
+        // Leftover seconds count towards the next minute
+        self.updated_at += elapsed * 60;
+        let minutes = self.minutes + elapsed;
+        self.minutes = minutes % MINUTES_PER_DAY;
+        self.days = (self.days + minutes / MINUTES_PER_DAY) & 0xFFF;
+    }
+
+    /// Runs the last written command and returns whether it changed the
+    /// memory or the clock.
+    fn execute(&mut self) -> bool {
+        let argument = self.command & 0x0F;
+        match self.command >> 4 & 0x07 {
+            // Read and post-increment
+            0x1 => {
+                self.response = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+                false
+            }
+            // Write and post-increment
+            0x3 => {
+                self.memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+                true
+            }
+            0x4 => {
+                self.address = (self.address & 0xF0) | argument;
+                false
+            }
+            0x5 => {
+                self.address = (self.address & 0x0F) | argument << 4;
+                false
+            }
+            0x6 => match argument {
+                // Copy the clock into memory
+                0x0 => {
+                    self.update();
+                    self.store_nibbles(0x00, self.minutes);
+                    self.store_nibbles(0x03, self.days);
+                    true
+                }
+                // Set the clock from memory
+                0x1 => {
+                    self.minutes = self.load_nibbles(0x00) % MINUTES_PER_DAY;
+                    self.days = self.load_nibbles(0x03);
+                    self.updated_at = now();
+                    true
+                }
+                // Status query, always answered with "ready"
+                0x2 => {
+                    self.response = 0x1;
+                    false
+                }
+                _ => {
+                    debug!("unhandled HuC3 extended command {:02X}", self.command);
+                    false
+                }
+            },
+            _ => {
+                debug!("unhandled HuC3 command {:02X}", self.command);
+                false
+            }
+        }
+    }
+
+    /// Stores a 12-bit value in three nibbles, least significant first.
+    fn store_nibbles(&mut self, address: usize, value: u64) {
+        for i in 0..3 {
+            self.memory[address + i] = (value >> (4 * i)) as u8 & 0x0F;
+        }
+    }
+
+    fn load_nibbles(&self, address: usize) -> u64 {
+        (0..3).fold(0, |value, i| {
+            value | (self.memory[address + i] as u64 & 0x0F) << (4 * i)
+        })
+    }
+}
+
+impl Mbc for HuC3 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.mode = match value & 0x0F {
+                    0x0 => Mode::RamReadOnly,
+                    0xA => Mode::RamReadWrite,
+                    0xB => Mode::Command,
+                    0xC => Mode::Response,
+                    0xD => Mode::Semaphore,
+                    0xE => Mode::Infrared,
+                    _ => Mode::Unmapped,
+                }
+            }
+            0x2000..=0x3FFF => self.rom_bank = value & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = value & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match self.mode {
+            Mode::RamReadOnly | Mode::RamReadWrite => {
+                match ram_index(ram, self.ram_bank as usize, address) {
+                    Some(index) => ram[index],
+                    None => 0xFF,
+                }
+            }
+            Mode::Response => 0x80 | (self.command & 0x70) | self.response,
+            // Commands complete immediately, so the clock is always ready
+            Mode::Semaphore => 0xFF,
+            // No light seen
+            Mode::Infrared => 0xC0,
+            Mode::Command | Mode::Unmapped => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        match self.mode {
+            Mode::RamReadWrite => store(ram, self.ram_bank as usize, address, value),
+            Mode::Command => {
+                self.command = value;
+                false
+            }
+            // Clearing bit 0 runs the last written command
+            Mode::Semaphore if value & 0x01 == 0 => self.execute(),
+            _ => false,
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.mode == Mode::RamReadWrite
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        data.extend_from_slice(&(self.minutes as u32).to_le_bytes());
+        data.extend_from_slice(&(self.days as u32).to_le_bytes());
+        data.extend_from_slice(&self.updated_at.to_le_bytes());
+        data
+    }
+
+    fn load_state(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE {
+            warn!("save file has no clock data, resetting the clock");
+            return;
+        }
+        let minutes = u32::from_le_bytes(data[0..4].try_into().unwrap()) as u64;
+        self.minutes = minutes % MINUTES_PER_DAY;
+        self.days = u32::from_le_bytes(data[4..8].try_into().unwrap()) as u64 & 0xFFF;
+        self.updated_at = u64::from_le_bytes(data[8..16].try_into().unwrap());
+        self.update();
+    }
+}
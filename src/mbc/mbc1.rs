@@ -0,0 +1,81 @@
+use super::{ram_index, rom_byte, store, Mbc};
+
+/// Supports up to 2 MiB of ROM and 32 KiB of RAM. The 2-bit upper register
+/// either extends the ROM bank number or, in RAM banking mode, selects the
+/// RAM bank. Mode 1 also applies it to the 0x0000-0x3FFF area.
+///
+/// Multicarts (MBC1M) don't connect bit 4 of the lower register, so the
+/// upper register starts at bit 4 of the bank number instead of bit 5.
+pub struct Mbc1 {
+    multicart: bool,
+    ram_enabled: bool,
+    /// Lower 5 bits of the ROM bank number; 0 is mapped to 1.
+    bank1: u8,
+    bank2: u8,
+    mode: u8,
+}
+
+impl Mbc1 {
+    pub fn new(multicart: bool) -> Self {
+        Self {
+            multicart,
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: 0,
+        }
+    }
+
+    fn upper_bank(&self) -> usize {
+        let shift = if self.multicart { 4 } else { 5 };
+        (self.bank2 as usize) << shift
+    }
+
+    fn lower_bank(&self) -> usize {
+        let mask = if self.multicart { 0x0F } else { 0x1F };
+        (self.bank1 & mask) as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.mode == 1 {
+            self.bank2 as usize
+        } else {
+            0
+        }
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF if self.mode == 1 => self.upper_bank(),
+            0x0000..=0x3FFF => 0,
+            _ => self.upper_bank() | self.lower_bank(),
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.bank1 = (value & 0x1F).max(1),
+            0x4000..=0x5FFF => self.bank2 = value & 0x03,
+            _ => self.mode = value & 0x01,
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match ram_index(ram, self.ram_bank(), address) {
+            Some(index) if self.ram_enabled => ram[index],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        self.ram_enabled && store(ram, self.ram_bank(), address, value)
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+}
@@ -0,0 +1,59 @@
+use super::{rom_byte, Mbc};
+
+/// Size of the built-in RAM, in 4-bit values.
+pub const RAM_SIZE: usize = 0x200;
+
+/// Supports up to 256 KiB of ROM and has 512 half-bytes of RAM built in,
+/// mirrored across the whole 0xA000-0xBFFF area. Bit 8 of the address
+/// decides which register a write to 0x0000-0x3FFF goes to.
+pub struct Mbc2 {
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x3FFF if address & 0x100 == 0 => self.ram_enabled = value & 0x0F == 0x0A,
+            0x0000..=0x3FFF => self.rom_bank = (value & 0x0F).max(1),
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        // Only the lower nibble exists, the upper one is open bus
+        0xF0 | ram[address as usize & (RAM_SIZE - 1)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+        let index = address as usize & (RAM_SIZE - 1);
+        std::mem::replace(&mut ram[index], value & 0x0F) != value & 0x0F
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+}
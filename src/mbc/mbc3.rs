@@ -0,0 +1,262 @@
+use std::convert::TryInto;
+
+use super::{now, ram_index, rom_byte, store, Mbc};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const DAY_HIGH: u8 = 1 << 0;
+const HALT: u8 = 1 << 6;
+const DAY_CARRY: u8 = 1 << 7;
+/// Size of the clock state appended to the save file: the current and
+/// latched registers as 32-bit values, followed by a 64-bit UNIX timestamp.
+const RTC_SAVE_SIZE: usize = 48;
+
+/// The clock registers 0x08-0x0C: seconds, minutes, hours, the lower 8 bits
+/// of the day counter, and the 9th day bit together with halt and carry.
+#[derive(Clone, Copy, Default)]
+struct RtcRegisters([u8; 5]);
+
+/// Real-time clock that follows the host's wall clock. It only advances
+/// when accessed, by however much time passed since the previous access.
+struct Rtc {
+    registers: RtcRegisters,
+    latched: RtcRegisters,
+    latch_armed: bool,
+    /// Host time the registers were last brought up to date.
+    updated_at: u64,
+}
+
+impl Rtc {
+    fn new() -> Self {
+        Self {
+            registers: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            latch_armed: false,
+            updated_at: now(),
+        }
+    }
+
+    fn update(&mut self) {
+        let now = now();
+        let elapsed = now.saturating_sub(self.updated_at);
+        self.updated_at = now;
+        let [seconds, minutes, hours, day_low, day_high] = &mut self.registers.0;
+        if *day_high & HALT != 0 || elapsed == 0 {
+            return;
+        }
+
+        let day = ((*day_high & DAY_HIGH) as u64) << 8 | *day_low as u64;
+        let total = *seconds as u64
+            + *minutes as u64 * 60
+            + *hours as u64 * 60 * 60
+            + day * SECONDS_PER_DAY
+            + elapsed;
+        *seconds = (total % 60) as u8;
+        *minutes = (total / 60 % 60) as u8;
+        *hours = (total / (60 * 60) % 24) as u8;
+        let day = total / SECONDS_PER_DAY;
+        if day > 0x1FF {
+            *day_high |= DAY_CARRY;
+        }
+        *day_low = day as u8;
+        *day_high = (*day_high & !DAY_HIGH) | ((day >> 8) as u8 & DAY_HIGH);
+    }
+
+    /// Writing 0x00 and then 0x01 copies the running clock into the
+    /// registers the game can read.
+    fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.update();
+            self.latched = self.registers;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        let value = self.latched.0[(register - 0x08) as usize];
+        match register {
+            0x08 | 0x09 => value & 0x3F,
+            0x0A => value & 0x1F,
+            0x0B => value,
+            _ => value & (DAY_HIGH | HALT | DAY_CARRY),
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        self.update();
+        self.registers.0[(register - 0x08) as usize] = value;
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        for register in self.registers.0.iter().chain(self.latched.0.iter()) {
+            data.extend_from_slice(&(*register as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&self.updated_at.to_le_bytes());
+        data
+    }
+
+    fn load_state(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE {
+            warn!("save file has no clock data, resetting the clock");
+            return;
+        }
+        let register = |index: usize| data[index * 4];
+        for index in 0..5 {
+            self.registers.0[index] = register(index);
+            self.latched.0[index] = register(index + 5);
+        }
+        self.updated_at = u64::from_le_bytes(data[40..48].try_into().unwrap());
+        // Catch up on the time that passed while the emulator wasn't running
+        self.update();
+    }
+}
+
+/// Supports up to 2 MiB of ROM, 32 KiB of RAM and optionally a real-time
+/// clock, whose registers are mapped in place of RAM banks 0x08-0x0C.
+pub struct Mbc3 {
+    rtc: Option<Rtc>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+}
+
+impl Mbc3 {
+    pub fn new(has_rtc: bool) -> Self {
+        Self {
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x7F).max(1),
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            _ => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(value);
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        match (self.ram_bank, &self.rtc) {
+            (0x00..=0x03, _) => match ram_index(ram, self.ram_bank as usize, address) {
+                Some(index) => ram[index],
+                None => 0xFF,
+            },
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+        match (self.ram_bank, &mut self.rtc) {
+            (0x00..=0x03, _) => store(ram, self.ram_bank as usize, address, value),
+            (0x08..=0x0C, Some(rtc)) => {
+                rtc.write(self.ram_bank, value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        match &self.rtc {
+            Some(rtc) => rtc.save_state(),
+            None => Vec::new(),
+        }
+    }
+
+    fn load_state(&mut self, data: &[u8]) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.load_state(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtc_save_layout() {
+        let mut rtc = Rtc::new();
+        rtc.registers = RtcRegisters([1, 2, 3, 4, HALT | DAY_HIGH]);
+        rtc.latched = RtcRegisters([6, 7, 8, 9, 10]);
+        rtc.updated_at = 0x0102_0304_0506_0708;
+
+        let data = rtc.save_state();
+        assert_eq!(data.len(), RTC_SAVE_SIZE);
+        let expected_registers = [1, 2, 3, 4, HALT | DAY_HIGH, 6, 7, 8, 9, 10];
+        for (index, register) in expected_registers.iter().enumerate() {
+            assert_eq!(data[index * 4..index * 4 + 4], [*register, 0, 0, 0]);
+        }
+        assert_eq!(
+            data[40..48],
+            [0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]
+        );
+    }
+
+    #[test]
+    fn rtc_round_trip() {
+        // A halted clock doesn't catch up, so everything comes back as saved
+        let mut rtc = Rtc::new();
+        rtc.registers = RtcRegisters([59, 59, 23, 0xFF, HALT | DAY_HIGH]);
+        rtc.latched = RtcRegisters([1, 2, 3, 4, 0]);
+        rtc.updated_at = now() - 1000;
+
+        let mut loaded = Rtc::new();
+        loaded.load_state(&rtc.save_state());
+        assert_eq!(loaded.registers.0, rtc.registers.0);
+        assert_eq!(loaded.latched.0, rtc.latched.0);
+    }
+
+    #[test]
+    fn rtc_catches_up_on_load() {
+        let mut rtc = Rtc::new();
+        rtc.registers = RtcRegisters([0, 30, 12, 0xFF, DAY_HIGH]);
+        rtc.updated_at = now() - (SECONDS_PER_DAY + 60 * 60 + 60 + 10);
+
+        let mut loaded = Rtc::new();
+        loaded.load_state(&rtc.save_state());
+        let [seconds, minutes, hours, day_low, day_high] = loaded.registers.0;
+        // A second may pass between saving and loading
+        assert!((10..=11).contains(&seconds));
+        assert_eq!((minutes, hours), (31, 13));
+        // Day 0x1FF rolls over into day 0 with the carry set
+        assert_eq!((day_low, day_high), (0x00, DAY_CARRY));
+    }
+
+    #[test]
+    fn rtc_missing_from_save() {
+        let mut mbc = Mbc3::new(true);
+        mbc.load_state(&[]);
+        assert_eq!(mbc.save_state().len(), RTC_SAVE_SIZE);
+        assert!(Mbc3::new(false).save_state().is_empty());
+    }
+}
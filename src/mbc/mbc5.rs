@@ -0,0 +1,68 @@
+use super::{ram_index, rom_byte, store, Mbc};
+
+const RUMBLE_MOTOR: u8 = 1 << 3;
+
+/// Supports up to 8 MiB of ROM through a 9-bit bank number, in which bank 0
+/// can be mapped to 0x4000-0x7FFF as well, and up to 128 KiB of RAM. On
+/// rumble cartridges bit 3 of the RAM bank register drives the motor.
+pub struct Mbc5 {
+    rumble: bool,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+}
+
+impl Mbc5 {
+    pub fn new(rumble: bool) -> Self {
+        Self {
+            rumble,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    fn ram_bank(&self) -> usize {
+        let mask = if self.rumble { 0x07 } else { 0x0F };
+        (self.ram_bank & mask) as usize
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 1) << 8),
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match ram_index(ram, self.ram_bank(), address) {
+            Some(index) if self.ram_enabled => ram[index],
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        self.ram_enabled && store(ram, self.ram_bank(), address, value)
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble && self.ram_bank & RUMBLE_MOTOR != 0
+    }
+}
@@ -0,0 +1,379 @@
+use super::{rom_byte, Mbc};
+
+/// The 93LC56 EEPROM holds 128 16-bit words.
+pub const EEPROM_SIZE: usize = 0x100;
+/// Accelerometer reading when level, and how much 1g changes it.
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+const ACCELEROMETER_GRAVITY: f32 = 0x70 as f32;
+
+const EEPROM_DO: u8 = 1 << 0;
+const EEPROM_DI: u8 = 1 << 1;
+const EEPROM_CLK: u8 = 1 << 6;
+const EEPROM_CS: u8 = 1 << 7;
+
+enum EepromState {
+    /// Waiting for a start bit.
+    Idle,
+    /// Shifting in the 2-bit opcode and 8-bit address.
+    Command { bits: u8, shift: u16 },
+    /// Shifting out a word, most significant bit first.
+    Read { bits: u8, word: u16 },
+    /// Shifting in a word, for one address or for all of them.
+    Write {
+        bits: u8,
+        shift: u16,
+        address: Option<u8>,
+    },
+}
+
+/// The serial EEPROM used as save memory. It is bit-banged through a single
+/// register: commands and data are clocked in on DI and read back on DO,
+/// one bit per rising edge of CLK while CS is high.
+struct Eeprom {
+    state: EepromState,
+    pins: u8,
+    data_out: bool,
+    write_enabled: bool,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Self {
+            state: EepromState::Idle,
+            pins: 0,
+            data_out: true,
+            write_enabled: false,
+        }
+    }
+
+    fn read(&self) -> u8 {
+        let data_out = if self.data_out { EEPROM_DO } else { 0 };
+        (self.pins & (EEPROM_CS | EEPROM_CLK | EEPROM_DI)) | data_out
+    }
+
+    /// Returns whether the write made the chip program any of its words.
+    fn write(&mut self, ram: &mut [u8], value: u8) -> bool {
+        let rising_edge = self.pins & EEPROM_CLK == 0 && value & EEPROM_CLK != 0;
+        self.pins = value;
+        if value & EEPROM_CS == 0 {
+            self.state = EepromState::Idle;
+            // Writes complete instantly, so the chip always reports ready
+            self.data_out = true;
+            return false;
+        }
+        rising_edge && self.clock(ram, value & EEPROM_DI != 0)
+    }
+
+    fn clock(&mut self, ram: &mut [u8], bit: bool) -> bool {
+        let mut changed = false;
+        self.state = match std::mem::replace(&mut self.state, EepromState::Idle) {
+            EepromState::Idle if bit => EepromState::Command { bits: 0, shift: 0 },
+            EepromState::Idle => EepromState::Idle,
+            EepromState::Command { bits, shift } => {
+                let shift = shift << 1 | bit as u16;
+                if bits + 1 < 10 {
+                    EepromState::Command {
+                        bits: bits + 1,
+                        shift,
+                    }
+                } else {
+                    self.command(ram, shift, &mut changed)
+                }
+            }
+            EepromState::Read { bits, word } => {
+                self.data_out = word & (0x8000 >> bits) != 0;
+                if bits + 1 < 16 {
+                    EepromState::Read {
+                        bits: bits + 1,
+                        word,
+                    }
+                } else {
+                    EepromState::Idle
+                }
+            }
+            EepromState::Write {
+                bits,
+                shift,
+                address,
+            } => {
+                let shift = shift << 1 | bit as u16;
+                if bits + 1 < 16 {
+                    EepromState::Write {
+                        bits: bits + 1,
+                        shift,
+                        address,
+                    }
+                } else {
+                    changed = fill(ram, address, shift);
+                    EepromState::Idle
+                }
+            }
+        };
+        changed
+    }
+
+    /// Decodes a command, setting `changed` if it programmed any words.
+    fn command(&mut self, ram: &mut [u8], command: u16, changed: &mut bool) -> EepromState {
+        let address = (command & 0x7F) as u8;
+        match command >> 8 {
+            // READ, preceded by a dummy 0 bit
+            0b10 => {
+                self.data_out = false;
+                EepromState::Read {
+                    bits: 0,
+                    word: read_word(ram, address),
+                }
+            }
+            0b01 if self.write_enabled => EepromState::Write {
+                bits: 0,
+                shift: 0,
+                address: Some(address),
+            },
+            // ERASE
+            0b11 if self.write_enabled => {
+                *changed = fill(ram, Some(address), 0xFFFF);
+                EepromState::Idle
+            }
+            0b00 => match command >> 6 & 0b11 {
+                // EWDS
+                0b00 => {
+                    self.write_enabled = false;
+                    EepromState::Idle
+                }
+                // WRAL
+                0b01 if self.write_enabled => EepromState::Write {
+                    bits: 0,
+                    shift: 0,
+                    address: None,
+                },
+                // ERAL
+                0b10 if self.write_enabled => {
+                    *changed = fill(ram, None, 0xFFFF);
+                    EepromState::Idle
+                }
+                // EWEN
+                0b11 => {
+                    self.write_enabled = true;
+                    EepromState::Idle
+                }
+                _ => EepromState::Idle,
+            },
+            _ => EepromState::Idle,
+        }
+    }
+}
+
+fn read_word(ram: &[u8], address: u8) -> u16 {
+    let index = address as usize * 2;
+    u16::from_le_bytes([ram[index], ram[index + 1]])
+}
+
+/// Programs one word, or all of them, and returns whether that changed any.
+fn fill(ram: &mut [u8], address: Option<u8>, value: u16) -> bool {
+    let words = match address {
+        Some(address) => address..=address,
+        None => 0..=0x7F,
+    };
+    words.fold(false, |changed, address| {
+        let index = address as usize * 2;
+        let before = read_word(ram, address);
+        ram[index..index + 2].copy_from_slice(&value.to_le_bytes());
+        changed | (before != value)
+    })
+}
+
+/// Kirby Tilt 'n' Tumble's mapper, with a 2-axis accelerometer and an
+/// EEPROM instead of RAM. Both are only reachable through 0xA000-0xAFFF
+/// after the two RAM enable registers have been set.
+pub struct Mbc7 {
+    ram_enabled: bool,
+    sensor_enabled: bool,
+    rom_bank: u8,
+    eeprom: Eeprom,
+    tilt: (u16, u16),
+    latched: (u16, u16),
+    latch_armed: bool,
+}
+
+impl Mbc7 {
+    pub fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            sensor_enabled: false,
+            rom_bank: 1,
+            eeprom: Eeprom::new(),
+            tilt: (ACCELEROMETER_CENTER as u16, ACCELEROMETER_CENTER as u16),
+            latched: (0x8000, 0x8000),
+            latch_armed: false,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.ram_enabled && self.sensor_enabled
+    }
+}
+
+impl Mbc for Mbc7 {
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_byte(rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x7F,
+            0x4000..=0x5FFF => self.sensor_enabled = value == 0x40,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, _ram: &[u8], address: u16) -> u8 {
+        if !self.enabled() || address >= 0xB000 {
+            return 0xFF;
+        }
+        match address & 0xF0 {
+            0x20 => self.latched.0 as u8,
+            0x30 => (self.latched.0 >> 8) as u8,
+            0x40 => self.latched.1 as u8,
+            0x50 => (self.latched.1 >> 8) as u8,
+            0x60 => 0x00,
+            0x80 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        if !self.enabled() || address >= 0xB000 {
+            return false;
+        }
+        match address & 0xF0 {
+            // Writing 0x55 and then 0xAA samples the accelerometer
+            0x00 if value == 0x55 => {
+                self.latched = (0x8000, 0x8000);
+                self.latch_armed = true;
+            }
+            0x10 if value == 0xAA && self.latch_armed => {
+                self.latched = self.tilt;
+                self.latch_armed = false;
+            }
+            0x80 => return self.eeprom.write(ram, value),
+            _ => (),
+        }
+        false
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.enabled()
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        let axis = |g: f32| {
+            (ACCELEROMETER_CENTER + g * ACCELEROMETER_GRAVITY).clamp(0.0, u16::MAX as f32) as u16
+        };
+        self.tilt = (axis(x), axis(y));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled_mbc7() -> Mbc7 {
+        let mut mbc = Mbc7::new();
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x40);
+        mbc
+    }
+
+    /// Clocks one bit into the EEPROM and returns DO after the rising edge.
+    fn clock_bit(mbc: &mut Mbc7, ram: &mut [u8], bit: bool) -> bool {
+        let di = if bit { EEPROM_DI } else { 0 };
+        mbc.write_ram(ram, 0xA080, EEPROM_CS | di);
+        mbc.write_ram(ram, 0xA080, EEPROM_CS | EEPROM_CLK | di);
+        mbc.read_ram(ram, 0xA080) & EEPROM_DO != 0
+    }
+
+    fn clock_bits(mbc: &mut Mbc7, ram: &mut [u8], value: u16, count: u8) {
+        for i in (0..count).rev() {
+            clock_bit(mbc, ram, value & (1 << i) != 0);
+        }
+    }
+
+    /// Sends the start bit followed by a 2-bit opcode and 8-bit address.
+    fn command(mbc: &mut Mbc7, ram: &mut [u8], command: u16) {
+        mbc.write_ram(ram, 0xA080, 0);
+        clock_bit(mbc, ram, true);
+        clock_bits(mbc, ram, command, 10);
+    }
+
+    #[test]
+    fn eeprom_write_then_read() {
+        let mut mbc = enabled_mbc7();
+        let mut ram = [0xFF; EEPROM_SIZE];
+
+        // EWEN, then WRITE 0xBEEF to word 0x12
+        command(&mut mbc, &mut ram, 0b00_1100_0000);
+        command(&mut mbc, &mut ram, 0b01 << 8 | 0x12);
+        clock_bits(&mut mbc, &mut ram, 0xBEEF >> 1, 15);
+        // The last data bit programs the word
+        mbc.write_ram(&mut ram, 0xA080, EEPROM_CS | EEPROM_DI);
+        assert!(mbc.write_ram(&mut ram, 0xA080, EEPROM_CS | EEPROM_CLK | EEPROM_DI));
+        mbc.write_ram(&mut ram, 0xA080, 0);
+        assert_eq!(ram[0x24..0x26], [0xEF, 0xBE]);
+
+        // READ shifts out a dummy 0 bit before the word
+        command(&mut mbc, &mut ram, 0b10 << 8 | 0x12);
+        assert_eq!(mbc.read_ram(&ram, 0xA080) & EEPROM_DO, 0);
+        let word = (0..16).fold(0u16, |word, _| {
+            word << 1 | clock_bit(&mut mbc, &mut ram, false) as u16
+        });
+        assert_eq!(word, 0xBEEF);
+    }
+
+    #[test]
+    fn eeprom_starts_erased() {
+        let cartridge = crate::cartridge::Cartridge::blank(0x22);
+        assert_eq!(cartridge.save_data(), [0xFF; EEPROM_SIZE]);
+    }
+
+    #[test]
+    fn eeprom_write_protected() {
+        let mut mbc = enabled_mbc7();
+        let mut ram = [0xFF; EEPROM_SIZE];
+
+        // Without EWEN the WRITE is ignored and the data bits go nowhere
+        command(&mut mbc, &mut ram, 0b01 << 8 | 0x12);
+        clock_bits(&mut mbc, &mut ram, 0xBEEF, 16);
+        assert_eq!(ram, [0xFF; EEPROM_SIZE]);
+    }
+
+    fn read_axes(mbc: &Mbc7) -> (u16, u16) {
+        let byte = |address| mbc.read_ram(&[], address) as u16;
+        (
+            byte(0xA030) << 8 | byte(0xA020),
+            byte(0xA050) << 8 | byte(0xA040),
+        )
+    }
+
+    #[test]
+    fn accelerometer_latch() {
+        let mut mbc = enabled_mbc7();
+        let mut ram = [0xFF; EEPROM_SIZE];
+        mbc.set_tilt(0.5, -1.0);
+
+        // Latching never counts as a change to the save data
+        assert!(!mbc.write_ram(&mut ram, 0xA000, 0x55));
+        assert_eq!(read_axes(&mbc), (0x8000, 0x8000));
+        assert!(!mbc.write_ram(&mut ram, 0xA010, 0xAA));
+        assert_eq!(read_axes(&mbc), (0x8208, 0x8160));
+
+        // 0xAA only latches right after 0x55
+        mbc.set_tilt(0.0, 0.0);
+        mbc.write_ram(&mut ram, 0xA010, 0xAA);
+        assert_eq!(read_axes(&mbc), (0x8208, 0x8160));
+    }
+}
@@ -1,39 +1,75 @@
-use std::io;
-
-use crate::gpu::GPU;
+use crate::apu::APU;
+use crate::cartridge::Cartridge;
+use crate::dma::Dma;
+use crate::gpu::{Mode, GPU};
+use crate::hdma::Hdma;
+use crate::interrupts::InterruptController;
+use crate::joypad::Joypad;
+use crate::mbc::ImageSource;
+use crate::model::Model;
+use crate::serial::Serial;
+use crate::sgb::Sgb;
+use crate::timer::Timer;
 
 pub struct MemoryBus {
-    rom: [u8; 0x8000],
-    eram: [u8; 0x2000],
-    wram: [u8; 0x2000],
+    model: Model,
+    /// Whether CGB features are enabled. A CGB runs games without CGB
+    /// support in a DMG compatibility mode, chosen by its boot ROM.
+    cgb_mode: bool,
+    cartridge: Cartridge,
+    /// Mapped over the cartridge until 0xFF50 is written. The CGB one also
+    /// covers 0x0200-0x08FF, leaving the cartridge header visible.
+    boot_rom: Option<Vec<u8>>,
+    /// Eight banks of 4 KiB. Bank 0 is fixed at 0xC000, the one at 0xD000
+    /// can only be switched on the CGB.
+    wram: [u8; 0x8000],
+    /// SVBK as written. Bank 0 selects bank 1.
+    wram_bank: u8,
     zram: [u8; 0xFF],
     gpu: GPU,
+    pub interrupts: InterruptController,
+    pub joypad: Joypad,
+    serial: Serial,
+    timer: Timer,
+    apu: APU,
+    dma: Dma,
+    hdma: Hdma,
+    /// RP (0xFF56) as written. Nothing ever sends an infrared signal.
+    infrared: u8,
+    sgb: Option<Sgb>,
+    double_speed: bool,
+    speed_switch_armed: bool,
+    frame_done: bool,
 }
 
 impl MemoryBus {
-    pub fn new() -> Self {
-        let mut bus = Self {
-            rom: [0; 0x8000],
-            eram: [0; 0x2000],
-            wram: [0; 0x2000],
-            zram: [0; 0xFF],
-            gpu: GPU::new(),
-        };
+    /// A bus in the state the boot ROM leaves it in.
+    pub fn new(model: Model, cartridge: Cartridge) -> Self {
+        let mut bus = Self::power_on(model, cartridge);
+        bus.cgb_mode = model.cgb_mode(&bus.cartridge.header);
+        bus.timer.set_divider(model.initial_divider());
+        // Both button groups are left selected. This bypasses the SGB, which
+        // would take the write for the start of a packet.
+        bus.joypad.write(0x00);
+        bus.write_byte(0xFF02, if bus.cgb_mode { 0x01 } else { 0x00 });
         bus.write_byte(0xFF05, 0x00);
         bus.write_byte(0xFF06, 0x00);
         bus.write_byte(0xFF07, 0x00);
+        // The boot ROM waits for VBlank without acknowledging it
+        bus.write_byte(0xFF0F, 0x01);
         bus.write_byte(0xFF10, 0x80);
         bus.write_byte(0xFF11, 0xBF);
         bus.write_byte(0xFF12, 0xF3);
+        bus.write_byte(0xFF13, 0xFF);
         bus.write_byte(0xFF14, 0xBF);
         bus.write_byte(0xFF16, 0x3F);
         bus.write_byte(0xFF17, 0x00);
+        bus.write_byte(0xFF18, 0xFF);
         bus.write_byte(0xFF19, 0xBF);
         bus.write_byte(0xFF1A, 0x7F);
         bus.write_byte(0xFF1B, 0xFF);
-        bus.write_byte(0xFF1C, 0xBF);
-        bus.write_byte(0xFF1B, 0xFF);
         bus.write_byte(0xFF1C, 0x9F);
+        bus.write_byte(0xFF1D, 0xFF);
         bus.write_byte(0xFF1E, 0xBF);
         bus.write_byte(0xFF20, 0xFF);
         bus.write_byte(0xFF21, 0x00);
@@ -41,8 +77,10 @@ impl MemoryBus {
         bus.write_byte(0xFF23, 0xBF);
         bus.write_byte(0xFF24, 0x77);
         bus.write_byte(0xFF25, 0xF3);
-        bus.write_byte(0xFF26, 0xF1);
-        bus.write_byte(0xFF26, 0xF0);
+        // Channel 1 is still on from the boot sound, which the SGB lacks
+        if !model.is_sgb() {
+            bus.apu.set_channels(0x01);
+        }
         bus.write_byte(0xFF40, 0x91);
         bus.write_byte(0xFF42, 0x00);
         bus.write_byte(0xFF43, 0x00);
@@ -51,52 +89,156 @@ impl MemoryBus {
         bus.write_byte(0xFF48, 0xFF);
         bus.write_byte(0xFF49, 0xFF);
         bus.write_byte(0xFF4A, 0x00);
-        bus.write_byte(0xFF4A, 0x00);
+        bus.write_byte(0xFF4B, 0x00);
         bus.write_byte(0xFFFF, 0x00);
+        if model.is_cgb() {
+            bus.dma.set_source(0x00);
+        }
+        // The DMG0 boot ROM is shorter and hands over during VBlank
+        if model == Model::Dmg0 {
+            bus.gpu.set_line(0x91);
+        }
         bus
     }
+
+    pub fn power_on(model: Model, cartridge: Cartridge) -> Self {
+        let sgb = if model.is_sgb() && cartridge.header.supports_sgb() {
+            Some(Sgb::new())
+        } else {
+            None
+        };
+        Self {
+            model,
+            cgb_mode: model.is_cgb(),
+            cartridge,
+            boot_rom: None,
+            wram: [0; 0x8000],
+            wram_bank: 0,
+            zram: [0; 0xFF],
+            gpu: GPU::new(),
+            interrupts: InterruptController::new(),
+            joypad: Joypad::new(),
+            serial: Serial::new(),
+            timer: Timer::new(),
+            apu: APU::new(),
+            dma: Dma::new(),
+            hdma: Hdma::new(),
+            infrared: 0,
+            sgb,
+            double_speed: false,
+            speed_switch_armed: false,
+            frame_done: false,
+        }
+    }
 }
 
 impl MemoryBus {
-    pub fn load<R: io::Read>(&mut self, data: &mut R) {
-        data.read(self.rom.as_mut()).expect("Could not load data");
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) -> Result<(), String> {
+        let expected = self.model.boot_rom_size();
+        if boot_rom.len() != expected {
+            return Err(format!(
+                "invalid boot ROM size for {:?}: expected {} bytes, got {}",
+                self.model,
+                expected,
+                boot_rom.len()
+            ));
+        }
+        self.boot_rom = Some(boot_rom);
+        Ok(())
     }
 
-    pub fn read_word(&self, address: u16) -> u16 {
-        let low = self.read_byte(address) as u16;
-        let high = self.read_byte(address + 1) as u16;
-        (high << 8) | low
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    pub fn speed_switch_armed(&self) -> bool {
+        self.speed_switch_armed
+    }
+
+    /// Toggles between normal and double speed mode, as STOP does when a
+    /// speed switch has been prepared through KEY1.
+    pub fn switch_speed(&mut self) {
+        self.double_speed = !self.double_speed;
+        self.speed_switch_armed = false;
+    }
+
+    /// Advances every peripheral by the given number of CPU cycles.
+    pub fn tick(&mut self, cycles: usize) {
+        for _ in 0..cycles / 4 {
+            if let Some((source, offset)) = self.dma.step() {
+                self.gpu.oam[offset] = self.read_byte(source);
+            }
+        }
+        self.timer.tick(cycles, &mut self.interrupts);
+        self.apu.tick(self.timer.divider(), self.double_speed);
+        self.serial.tick(cycles, &mut self.interrupts);
+        self.cartridge.tick(cycles);
+        // In double speed mode the LCD keeps running at the normal rate
+        let dots = if self.double_speed {
+            cycles / 2
+        } else {
+            cycles
+        };
+        let mode = self.gpu.mode;
+        self.frame_done |= self.gpu.tick(dots, &mut self.interrupts);
+        if mode != Mode::HBlank && self.gpu.mode == Mode::HBlank && self.hdma.hblank_pending() {
+            self.copy_hdma_block();
+        }
+    }
+
+    /// Whether the cartridge's rumble motor is on.
+    pub fn rumble(&self) -> bool {
+        self.cartridge.rumble()
+    }
+
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.cartridge.set_tilt(x, y);
+    }
+
+    pub fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.cartridge.set_image_source(source);
+    }
+
+    /// Returns whether the LCD finished a frame since the last call.
+    pub fn take_frame_done(&mut self) -> bool {
+        std::mem::replace(&mut self.frame_done, false)
     }
 
     pub fn read_byte(&self, address: u16) -> u8 {
         let addr = address as usize;
         match addr & 0xF000 {
-            0x0000 => {
-                // TODO: check for bios
-                self.rom[addr]
-            }
+            0x0000 => match &self.boot_rom {
+                Some(boot_rom) if addr < 0x100 || (0x200..boot_rom.len()).contains(&addr) => {
+                    boot_rom[addr]
+                }
+                _ => self.cartridge.read_rom(address),
+            },
             // ROM0
-            0x1000 | 0x2000 | 0x3000 => self.rom[addr],
+            0x1000 | 0x2000 | 0x3000 => self.cartridge.read_rom(address),
             // ROM1
-            0x4000 | 0x5000 | 0x6000 | 0x7000 => self.rom[addr],
+            0x4000 | 0x5000 | 0x6000 | 0x7000 => self.cartridge.read_rom(address),
             // VRAM
-            0x8000 | 0x9000 => self.gpu.ram[addr & 0x1FFF],
+            0x8000 | 0x9000 => self.gpu.ram[self.vram_index(addr)],
             // External RAM
-            0xA000 | 0xB000 => self.eram[addr & 0x1FFF],
+            0xA000 | 0xB000 => self.cartridge.read_ram(address),
             // Working RAM
-            0xC000 | 0xD000 => self.wram[addr & 0x1FFF],
+            0xC000 | 0xD000 => self.wram[self.wram_index(addr)],
             // Working RAM shadow
-            0xE000 => self.wram[addr & 0x1FFF],
+            0xE000 => self.wram[self.wram_index(addr)],
             0xF000 => match addr & 0x0F00 {
                 // Working RAM shadow
-                a if a < 0xD00 => self.wram[addr & 0x1FFF],
-                // Sprite attributes - only 160 bytes long
+                a if a < 0xD00 => self.wram[self.wram_index(addr)],
+                // Sprite attributes - only 160 bytes long, and
+                // inaccessible while DMA is writing to them
+                0xE00 if addr < 0xFEA0 && self.dma.active() => 0xFF,
                 0xE00 if addr < 0xFEA0 => self.gpu.oam[addr & 0xFF],
                 0xE00 => 0,
+                // Interrupt enable
+                0xF00 if addr == 0xFFFF => self.interrupts.read_enable(),
                 // Zero page
                 0xF00 if addr >= 0xFF80 => self.zram[addr & 0x7F],
-                // Unhandled
-                0xF00 => 0,
+                // I/O registers
+                0xF00 => self.read_io(address),
                 _ => unreachable!(),
             },
             _ => unreachable!(),
@@ -106,73 +248,198 @@ impl MemoryBus {
     pub fn write_byte(&mut self, address: u16, value: u8) {
         let addr = address as usize;
         match addr & 0xF000 {
-            0x0000 => {
-                // TODO: check for bios
-                self.rom[addr] = value
+            // ROM - writes go to the cartridge's bank controller
+            0x0000..=0x7000 => {
+                self.cartridge.write_rom(address, value);
             }
-            // ROM0
-            0x1000 | 0x2000 | 0x3000 => self.rom[addr] = value,
-            // ROM1
-            0x4000 | 0x5000 | 0x6000 | 0x7000 => self.rom[addr] = value,
             // VRAM
-            0x8000 | 0x9000 => self.gpu.ram[addr & 0x1FFF] = value,
+            0x8000 | 0x9000 => self.gpu.write_vram(self.vram_index(addr), value),
             // External RAM
-            0xA000 | 0xB000 => self.eram[addr & 0x1FFF] = value,
+            0xA000 | 0xB000 => {
+                self.cartridge.write_ram(address, value);
+            }
             // Working RAM
-            0xC000 | 0xD000 => self.wram[addr & 0x1FFF] = value,
+            0xC000 | 0xD000 => self.wram[self.wram_index(addr)] = value,
             // Working RAM shadow
-            0xE000 => self.wram[addr & 0x1FFF] = value,
+            0xE000 => self.wram[self.wram_index(addr)] = value,
             0xF000 => match addr & 0x0F00 {
                 // Working RAM shadow
-                a if a < 0xD00 => self.wram[addr & 0x1FFF] = value,
-                // Sprite attributes - only 160 bytes long
+                a if a < 0xD00 => self.wram[self.wram_index(addr)] = value,
+                // Sprite attributes - only 160 bytes long, and
+                // inaccessible while DMA is writing to them
+                0xE00 if addr < 0xFEA0 && self.dma.active() => (),
                 0xE00 if addr < 0xFEA0 => self.gpu.oam[addr & 0xFF] = value,
                 0xE00 => (),
+                // Interrupt enable
+                0xF00 if addr == 0xFFFF => self.interrupts.write_enable(value),
                 // Zero page
                 0xF00 if addr >= 0xFF80 => self.zram[addr & 0x7F] = value,
-                // Unhandled
-                0xF00 => (),
+                // I/O registers
+                0xF00 => self.write_io(address, value),
                 _ => unreachable!(),
             },
             _ => unreachable!(),
         }
     }
 
-    pub fn get_mut_byte(&mut self, address: u16) -> &mut u8 {
-        let addr = address as usize;
-        if addr == 0x021A {
-            debug!("DAFUQ");
+    fn copy_hdma_block(&mut self) {
+        let (source, destination) = self.hdma.next_block();
+        for offset in 0..0x10 {
+            let value = self.read_byte(source.wrapping_add(offset));
+            let index = self.vram_index(0x8000 | (destination + offset) as usize);
+            self.gpu.write_vram(index, value);
         }
-        match addr & 0xF000 {
-            0x0000 => {
-                // TODO: check for bios
-                &mut self.rom[addr]
+    }
+
+    fn vram_index(&self, addr: usize) -> usize {
+        self.gpu.vram_bank as usize * 0x2000 + (addr & 0x1FFF)
+    }
+
+    fn wram_index(&self, addr: usize) -> usize {
+        match addr & 0x1000 {
+            0 => addr & 0x0FFF,
+            _ => self.wram_bank.max(1) as usize * 0x1000 + (addr & 0x0FFF),
+        }
+    }
+
+    /// Routes reads from the 0xFF00-0xFF7F page to the owning peripheral.
+    /// Unused bits and unmapped registers read as 1s.
+    fn read_io(&self, address: u16) -> u8 {
+        match address {
+            0xFF00 => self.joypad.read(),
+            0xFF01 | 0xFF02 => self.serial.read(address),
+            0xFF04..=0xFF07 => self.timer.read(address),
+            0xFF0F => self.interrupts.read_flag(),
+            0xFF10..=0xFF3F => self.apu.read(address),
+            0xFF46 => self.dma.read(),
+            0xFF40..=0xFF4B => self.gpu.read_register(address),
+            // Prepare speed switch
+            0xFF4D if self.cgb_mode => {
+                0x7E | ((self.double_speed as u8) << 7) | self.speed_switch_armed as u8
             }
-            // ROM0
-            0x1000 | 0x2000 | 0x3000 => &mut self.rom[addr],
-            // ROM1
-            0x4000 | 0x5000 | 0x6000 | 0x7000 => &mut self.rom[addr],
-            // VRAM
-            0x8000 | 0x9000 => &mut self.gpu.ram[addr & 0x1FFF],
-            // External RAM
-            0xA000 | 0xB000 => &mut self.eram[addr & 0x1FFF],
-            // Working RAM
-            0xC000 | 0xD000 => &mut self.wram[addr & 0x1FFF],
-            // Working RAM shadow
-            0xE000 => &mut self.wram[addr & 0x1FFF],
-            0xF000 => match addr & 0x0F00 {
-                // Working RAM shadow
-                a if a < 0xD00 => &mut self.wram[addr & 0x1FFF],
-                // Sprite attributes - only 160 bytes long
-                0xE00 if addr < 0xFEA0 => &mut self.gpu.oam[addr & 0xFF],
-                0xE00 => panic!("can't get mutable borrow of address {:X}", addr),
-                // Zero page
-                0xF00 if addr >= 0xFF80 => &mut self.zram[addr & 0x7F],
-                // Unhandled
-                0xF00 => panic!("can't get mutable borrow of address {:X}", addr),
-                _ => unreachable!(),
-            },
-            _ => unreachable!(),
+            0xFF4F if self.cgb_mode => 0xFE | self.gpu.vram_bank,
+            // HDMA1-HDMA4 are write-only
+            0xFF55 if self.cgb_mode => self.hdma.read(),
+            // Bit 1 reads 0 while a signal is being received
+            0xFF56 if self.cgb_mode => 0x3E | self.infrared,
+            0xFF68..=0xFF6B if self.cgb_mode => self.gpu.read_palette(address),
+            0xFF70 if self.cgb_mode => 0xF8 | self.wram_bank,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_io(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF00 => {
+                self.joypad.write(value);
+                if let Some(sgb) = &mut self.sgb {
+                    sgb.write(value, &mut self.joypad);
+                }
+            }
+            0xFF01 | 0xFF02 => self.serial.write(address, value),
+            0xFF04..=0xFF07 => self.timer.write(address, value),
+            0xFF0F => self.interrupts.write_flag(value),
+            0xFF10..=0xFF3F => self.apu.write(address, value),
+            0xFF46 => self.dma.start(value),
+            0xFF40..=0xFF4B => self.gpu.write_register(address, value),
+            // Prepare speed switch
+            0xFF4D if self.cgb_mode => self.speed_switch_armed = value & 1 != 0,
+            0xFF4F if self.cgb_mode => self.gpu.vram_bank = value & 0x01,
+            0xFF51..=0xFF55 if self.cgb_mode => {
+                self.hdma.write(address, value);
+                while self.hdma.general_purpose_pending() {
+                    self.copy_hdma_block();
+                }
+            }
+            0xFF56 if self.cgb_mode => self.infrared = value & 0xC1,
+            0xFF68..=0xFF6B if self.cgb_mode => self.gpu.write_palette(address, value),
+            0xFF70 if self.cgb_mode => self.wram_bank = value & 0x07,
+            // The CGB boot ROM switches to DMG compatibility mode through
+            // KEY0, which locks once it is unmapped
+            0xFF4C if self.model.is_cgb() && self.boot_rom.is_some() => {
+                self.cgb_mode = value & 0x04 == 0
+            }
+            // Unmaps the boot ROM until the next reset
+            0xFF50 if value != 0 => self.boot_rom = None,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cgb_bus() -> MemoryBus {
+        MemoryBus::power_on(Model::Cgb, Cartridge::blank(0x00))
+    }
+
+    #[test]
+    fn hdma5_reads_ff_when_idle() {
+        let mut bus = cgb_bus();
+        assert_eq!(bus.read_byte(0xFF55), 0xFF);
+        for address in 0xFF51..=0xFF54 {
+            bus.write_byte(address, 0x12);
+            assert_eq!(bus.read_byte(address), 0xFF);
+        }
+    }
+
+    #[test]
+    fn general_purpose_hdma_copies_into_vram() {
+        let mut bus = cgb_bus();
+        for offset in 0..0x20 {
+            bus.write_byte(0xC000 + offset, offset as u8);
+        }
+        bus.write_byte(0xFF51, 0xC0);
+        bus.write_byte(0xFF52, 0x0F);
+        bus.write_byte(0xFF53, 0xE1);
+        bus.write_byte(0xFF54, 0x00);
+        bus.write_byte(0xFF55, 0x01);
+
+        // The low nibbles and the upper bits of the destination are ignored
+        for offset in 0..0x20 {
+            assert_eq!(bus.read_byte(0x8100 + offset), offset as u8);
+        }
+        assert_eq!(bus.read_byte(0xFF55), 0xFF);
+    }
+
+    #[test]
+    fn hblank_hdma_can_be_stopped() {
+        let mut bus = cgb_bus();
+        bus.write_byte(0xFF55, 0x83);
+        assert_eq!(bus.read_byte(0xFF55), 0x03);
+        bus.write_byte(0xFF55, 0x00);
+        assert_eq!(bus.read_byte(0xFF55), 0x83);
+    }
+
+    #[test]
+    fn palette_index_increments_after_writes() {
+        let mut bus = cgb_bus();
+        bus.write_byte(0xFF68, 0xBE);
+        bus.write_byte(0xFF69, 0x12);
+        bus.write_byte(0xFF69, 0x34);
+        assert_eq!(bus.read_byte(0xFF68), 0xC0);
+
+        bus.write_byte(0xFF68, 0x3E);
+        assert_eq!(bus.read_byte(0xFF68), 0x7E);
+        assert_eq!(bus.read_byte(0xFF69), 0x12);
+        bus.write_byte(0xFF68, 0x3F);
+        assert_eq!(bus.read_byte(0xFF69), 0x34);
+        // OCPD is separate
+        assert_eq!(bus.read_byte(0xFF6B), 0x00);
+    }
+
+    #[test]
+    fn cgb_registers_unmapped_in_dmg_mode() {
+        let mut bus = cgb_bus();
+        bus.cgb_mode = false;
+        bus.write_byte(0xFF68, 0x80);
+        bus.write_byte(0xFF56, 0x01);
+        for address in [0xFF55, 0xFF56, 0xFF68, 0xFF69, 0xFF6A, 0xFF6B] {
+            assert_eq!(bus.read_byte(address), 0xFF);
         }
+        bus.cgb_mode = true;
+        assert_eq!(bus.read_byte(0xFF68), 0x40);
+        assert_eq!(bus.read_byte(0xFF56), 0x3E);
     }
 }
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use crate::cartridge::Header;
+
+/// The hardware being emulated. Besides the features it has, it decides the
+/// state the boot ROM leaves behind, which games use to detect it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Model {
+    /// The original DMG with its early boot ROM.
+    Dmg0,
+    Dmg,
+    /// Game Boy Pocket and Light.
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb,
+    /// Game Boy Advance running a Game Boy cartridge.
+    Agb,
+}
+
+impl Model {
+    pub fn is_cgb(self) -> bool {
+        matches!(self, Model::Cgb | Model::Agb)
+    }
+
+    pub fn is_sgb(self) -> bool {
+        matches!(self, Model::Sgb | Model::Sgb2)
+    }
+
+    /// Whether the model runs the cartridge with CGB features enabled,
+    /// rather than in DMG compatibility mode.
+    pub fn cgb_mode(self, header: &Header) -> bool {
+        self.is_cgb() && header.supports_cgb()
+    }
+
+    /// AF, BC, DE and HL as the boot ROM leaves them for the given cartridge.
+    pub fn initial_registers(self, header: &Header) -> [u16; 4] {
+        match self {
+            Model::Dmg0 => [0x0100, 0xFF13, 0x00C1, 0x8403],
+            Model::Dmg | Model::Mgb => {
+                let a = if self == Model::Mgb { 0xFF } else { 0x01 };
+                // The header check leaves H and C set unless its sum is 0
+                let f = if header.header_checksum == 0 {
+                    0x80
+                } else {
+                    0xB0
+                };
+                [a << 8 | f, 0x0013, 0x00D8, 0x014D]
+            }
+            Model::Sgb => [0x0100, 0x0014, 0x0000, 0xC060],
+            Model::Sgb2 => [0xFF00, 0x0014, 0x0000, 0xC060],
+            Model::Cgb if header.supports_cgb() => [0x1180, 0x0000, 0xFF56, 0x000D],
+            Model::Agb if header.supports_cgb() => [0x1100, 0x0100, 0xFF56, 0x000D],
+            Model::Cgb | Model::Agb => {
+                // For DMG games B holds the title checksum used to look up
+                // a palette, but only for Nintendo's own games
+                let b = if header.is_nintendo() {
+                    header.title_checksum
+                } else {
+                    0x00
+                };
+                let hl = if b == 0x43 || b == 0x58 {
+                    0x991A
+                } else {
+                    0x007C
+                };
+                if self == Model::Cgb {
+                    [0x1180, (b as u16) << 8, 0x0008, hl]
+                } else {
+                    // The AGB boot ROM ends with an extra INC B
+                    let inc = b.wrapping_add(1);
+                    let z = if inc == 0 { 0x80 } else { 0x00 };
+                    let h = if b & 0x0F == 0x0F { 0x20 } else { 0x00 };
+                    [0x1100 | z | h, (inc as u16) << 8, 0x0008, hl]
+                }
+            }
+        }
+    }
+
+    /// The internal divider when the boot ROM hands over control.
+    pub fn initial_divider(self) -> u16 {
+        match self {
+            Model::Dmg0 => 0x1830,
+            Model::Dmg | Model::Mgb => 0xABCC,
+            Model::Sgb | Model::Sgb2 => 0xD85C,
+            Model::Cgb | Model::Agb => 0x267C,
+        }
+    }
+
+    pub fn boot_rom_size(self) -> usize {
+        if self.is_cgb() {
+            0x900
+        } else {
+            0x100
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dmg0" => Ok(Model::Dmg0),
+            "dmg" => Ok(Model::Dmg),
+            "mgb" => Ok(Model::Mgb),
+            "sgb" => Ok(Model::Sgb),
+            "sgb2" => Ok(Model::Sgb2),
+            "cgb" => Ok(Model::Cgb),
+            "agb" => Ok(Model::Agb),
+            _ => Err(format!("unknown model: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cartridge::Cartridge;
+
+/// Frames RAM has to stay disabled after a write before it gets saved.
+const SAVE_DELAY_FRAMES: usize = 60;
+/// Frames after which a write gets saved even if the game never disables
+/// RAM again.
+const MAX_SAVE_DELAY_FRAMES: usize = 60 * 10;
+
+/// The `.sav` file of a battery backed cartridge. Changes are written back
+/// once the game disables RAM again, which it does after finishing a save,
+/// and when the emulator exits.
+pub struct SaveFile {
+    path: PathBuf,
+    /// Frames since the first unsaved write, if any.
+    pending_frames: Option<usize>,
+    /// Frames RAM has been disabled for since the last write.
+    disabled_frames: usize,
+}
+
+impl SaveFile {
+    /// The save file for the given ROM, which lives next to it unless a
+    /// save directory is given.
+    pub fn new(rom_path: &Path, save_dir: Option<&Path>) -> Self {
+        let file_name = rom_path.with_extension("sav");
+        let path = match (save_dir, file_name.file_name()) {
+            (Some(dir), Some(name)) => dir.join(name),
+            _ => file_name,
+        };
+        Self {
+            path,
+            pending_frames: None,
+            disabled_frames: 0,
+        }
+    }
+
+    pub fn load(&self, cartridge: &mut Cartridge) -> io::Result<()> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                info!("loading save file {}", self.path.display());
+                cartridge.load_save_data(&data);
+                Ok(())
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Called once per frame, writes the save file when it is due.
+    pub fn update(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        if cartridge.take_dirty() {
+            self.pending_frames.get_or_insert(0);
+            self.disabled_frames = 0;
+        }
+        let pending_frames = match &mut self.pending_frames {
+            Some(frames) => {
+                *frames += 1;
+                *frames
+            }
+            None => return Ok(()),
+        };
+        if cartridge.ram_enabled() {
+            self.disabled_frames = 0;
+        } else {
+            self.disabled_frames += 1;
+        }
+        if self.disabled_frames >= SAVE_DELAY_FRAMES || pending_frames >= MAX_SAVE_DELAY_FRAMES {
+            if let Err(err) = self.write(cartridge) {
+                // Start the delay over instead of retrying every frame
+                self.pending_frames = Some(0);
+                self.disabled_frames = 0;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes any unsaved changes, for when the emulator exits. Cartridges
+    /// with a clock always get written, to keep its timestamp current.
+    pub fn flush(&mut self, cartridge: &mut Cartridge) -> io::Result<()> {
+        let dirty = cartridge.take_dirty();
+        if dirty || self.pending_frames.is_some() || cartridge.has_clock() {
+            self.write(cartridge)?;
+        }
+        Ok(())
+    }
+
+    /// Writes to a temporary file first and then renames it over the save,
+    /// so a crash halfway through leaves the old save intact.
+    fn write(&mut self, cartridge: &Cartridge) -> io::Result<()> {
+        let temp_path = self.path.with_extension("sav.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&cartridge.save_data())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+        debug!("wrote save file {}", self.path.display());
+        self.pending_frames = None;
+        self.disabled_frames = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flush_skips_unchanged_saves() {
+        let dir = std::env::temp_dir().join(format!("rgb-save-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut save_file = SaveFile::new(Path::new("game.gb"), Some(&dir));
+        let mut cartridge = Cartridge::blank(0x06);
+
+        save_file.flush(&mut cartridge).unwrap();
+        assert!(!save_file.path.exists());
+
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+        save_file.flush(&mut cartridge).unwrap();
+        assert!(save_file.path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
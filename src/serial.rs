@@ -0,0 +1,65 @@
+use crate::interrupts::{Interrupt, InterruptController};
+
+const TRANSFER_START: u8 = 1 << 7;
+const INTERNAL_CLOCK: u8 = 1 << 0;
+/// The internal clock shifts one bit every 512 cycles (8192 Hz).
+const CYCLES_PER_BIT: usize = 512;
+
+/// SB and SC. Nothing is ever connected to the link port, so every transfer
+/// driven by the internal clock shifts in 1s.
+pub struct Serial {
+    data: u8,
+    control: u8,
+    bits_remaining: u8,
+    cycles: usize,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            data: 0,
+            control: 0,
+            bits_remaining: 0,
+            cycles: 0,
+        }
+    }
+
+    pub fn tick(&mut self, cycles: usize, interrupts: &mut InterruptController) {
+        if self.bits_remaining == 0 {
+            return;
+        }
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_BIT && self.bits_remaining > 0 {
+            self.cycles -= CYCLES_PER_BIT;
+            self.data = (self.data << 1) | 1;
+            self.bits_remaining -= 1;
+            if self.bits_remaining == 0 {
+                self.control &= !TRANSFER_START;
+                interrupts.request(Interrupt::Serial);
+            }
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF01 => self.data,
+            0xFF02 => self.control | 0x7E,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF01 => self.data = value,
+            0xFF02 => {
+                self.control = value & (TRANSFER_START | INTERNAL_CLOCK);
+                if self.control == TRANSFER_START | INTERNAL_CLOCK {
+                    debug!("serial transfer: {:02X}", self.data);
+                    self.bits_remaining = 8;
+                    self.cycles = 0;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
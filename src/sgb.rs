@@ -0,0 +1,80 @@
+use crate::joypad::Joypad;
+
+const PACKET_SIZE: usize = 16;
+const MLT_REQ: u8 = 0x11;
+
+/// The Super Game Boy's command channel. Games send 16-byte packets by
+/// pulsing the two select lines of P1: both low starts a packet, P15 low
+/// sends a 1 and P14 low a 0, each followed by both high again.
+///
+/// Only MLT_REQ, which enables multiplayer and is used by many games to
+/// detect the SGB, has an effect; other commands are logged.
+pub struct Sgb {
+    packet: [u8; PACKET_SIZE],
+    /// Next bit of the packet being received, if any.
+    bit: Option<usize>,
+    /// Packets still to come for the current command.
+    remaining_packets: u8,
+    select: u8,
+    players: u8,
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        Self {
+            packet: [0; PACKET_SIZE],
+            bit: None,
+            remaining_packets: 0,
+            select: 0x30,
+            players: 1,
+        }
+    }
+
+    pub fn write(&mut self, value: u8, joypad: &mut Joypad) {
+        let select = value & 0x30;
+        match (select, self.bit) {
+            (0x00, _) => {
+                self.packet = [0; PACKET_SIZE];
+                self.bit = Some(0);
+            }
+            // The packet ends with a 0 bit
+            (0x10, Some(bit)) | (0x20, Some(bit)) if self.select == 0x30 => {
+                if bit == PACKET_SIZE * 8 {
+                    self.bit = None;
+                    self.receive();
+                } else {
+                    if select == 0x10 {
+                        self.packet[bit / 8] |= 1 << (bit % 8);
+                    }
+                    self.bit = Some(bit + 1);
+                }
+            }
+            // Releasing P15 outside of a packet selects the next player
+            (0x30, None) if self.select & 0x20 == 0 && self.players > 1 => {
+                joypad.player = (joypad.player + 1) % self.players;
+            }
+            _ => (),
+        }
+        self.select = select;
+    }
+
+    fn receive(&mut self) {
+        if self.remaining_packets > 0 {
+            self.remaining_packets -= 1;
+            return;
+        }
+        let command = self.packet[0] >> 3;
+        self.remaining_packets = (self.packet[0] & 0x07).saturating_sub(1);
+        match command {
+            MLT_REQ => {
+                self.players = match self.packet[1] & 0x03 {
+                    0x01 => 2,
+                    0x03 => 4,
+                    _ => 1,
+                };
+                debug!("SGB multiplayer with {} players", self.players);
+            }
+            _ => debug!("unhandled SGB command {:02X}", command),
+        }
+    }
+}
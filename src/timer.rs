@@ -0,0 +1,194 @@
+use crate::interrupts::{Interrupt, InterruptController};
+
+const TIMER_ENABLE: u8 = 1 << 2;
+
+/// DIV, TIMA, TMA and TAC. TIMA is clocked by the falling edge of one bit
+/// of the internal 16-bit divider (gated by the enable bit in TAC), which is
+/// why writes to DIV or TAC can increment it as well.
+pub struct Timer {
+    divider: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    /// TIMA overflowed during the last M-cycle and is still zero; TMA gets
+    /// loaded into it on the next one.
+    overflow: bool,
+    /// TMA was loaded into TIMA during the current M-cycle. Writes to TIMA
+    /// are ignored for that cycle and writes to TMA go through to TIMA.
+    reloaded: bool,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self {
+            divider: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            overflow: false,
+            reloaded: false,
+        }
+    }
+
+    pub fn set_divider(&mut self, divider: u16) {
+        self.divider = divider;
+    }
+
+    pub fn divider(&self) -> u16 {
+        self.divider
+    }
+
+    pub fn tick(&mut self, cycles: usize, interrupts: &mut InterruptController) {
+        for _ in 0..cycles / 4 {
+            self.step(interrupts);
+        }
+    }
+
+    fn step(&mut self, interrupts: &mut InterruptController) {
+        self.reloaded = false;
+        if self.overflow {
+            self.overflow = false;
+            self.tima = self.tma;
+            self.reloaded = true;
+            interrupts.request(Interrupt::Timer);
+        }
+        let input = self.input();
+        self.divider = self.divider.wrapping_add(4);
+        self.detect_falling_edge(input);
+    }
+
+    /// The divider bit selected by TAC, ANDed with the timer enable bit.
+    fn input(&self) -> bool {
+        let bit = match self.tac & 0b11 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+        self.tac & TIMER_ENABLE != 0 && self.divider & (1 << bit) != 0
+    }
+
+    fn detect_falling_edge(&mut self, previous_input: bool) {
+        if previous_input && !self.input() {
+            let (result, overflow) = self.tima.overflowing_add(1);
+            self.tima = result;
+            self.overflow |= overflow;
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF04 => (self.divider >> 8) as u8,
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            0xFF07 => self.tac | 0xF8,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF04 => {
+                let input = self.input();
+                self.divider = 0;
+                self.detect_falling_edge(input);
+            }
+            0xFF05 => {
+                if !self.reloaded {
+                    self.tima = value;
+                    // Writing during the overflow cycle cancels the reload
+                    self.overflow = false;
+                }
+            }
+            0xFF06 => {
+                self.tma = value;
+                if self.reloaded {
+                    self.tima = value;
+                }
+            }
+            0xFF07 => {
+                let input = self.input();
+                self.tac = value & 0x07;
+                self.detect_falling_edge(input);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMER_INTERRUPT: u8 = 1 << 2;
+
+    /// A timer clocked from divider bit 3 whose input is currently high.
+    fn running_timer(tima: u8, tma: u8) -> Timer {
+        let mut timer = Timer::new();
+        timer.write(0xFF05, tima);
+        timer.write(0xFF06, tma);
+        timer.write(0xFF07, TIMER_ENABLE | 0b01);
+        timer.set_divider(0x000C);
+        timer
+    }
+
+    #[test]
+    fn div_write_increments_tima() {
+        let mut timer = running_timer(0x10, 0x00);
+        timer.write(0xFF04, 0x00);
+        assert_eq!(timer.read(0xFF05), 0x11);
+        assert_eq!(timer.read(0xFF04), 0x00);
+    }
+
+    #[test]
+    fn tac_change_increments_tima() {
+        // Selecting a bit that is low is a falling edge on the input
+        let mut timer = running_timer(0x10, 0x00);
+        timer.write(0xFF07, TIMER_ENABLE);
+        assert_eq!(timer.read(0xFF05), 0x11);
+
+        // So is disabling the timer while the selected bit is high
+        let mut timer = running_timer(0x10, 0x00);
+        timer.write(0xFF07, 0b01);
+        assert_eq!(timer.read(0xFF05), 0x11);
+    }
+
+    #[test]
+    fn overflow_delays_reload() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = running_timer(0xFF, 0x42);
+
+        timer.tick(4, &mut interrupts);
+        assert_eq!(timer.read(0xFF05), 0x00);
+        assert_eq!(interrupts.read_flag() & TIMER_INTERRUPT, 0);
+
+        timer.tick(4, &mut interrupts);
+        assert_eq!(timer.read(0xFF05), 0x42);
+        assert_eq!(interrupts.read_flag() & TIMER_INTERRUPT, TIMER_INTERRUPT);
+    }
+
+    #[test]
+    fn tima_write_during_reload_is_ignored() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = running_timer(0xFF, 0x42);
+        timer.tick(8, &mut interrupts);
+
+        timer.write(0xFF05, 0x10);
+        assert_eq!(timer.read(0xFF05), 0x42);
+        // TMA writes in the same cycle go through to TIMA instead
+        timer.write(0xFF06, 0x24);
+        assert_eq!(timer.read(0xFF05), 0x24);
+    }
+
+    #[test]
+    fn tima_write_during_overflow_cancels_reload() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = running_timer(0xFF, 0x42);
+        timer.tick(4, &mut interrupts);
+
+        timer.write(0xFF05, 0x10);
+        timer.tick(4, &mut interrupts);
+        assert_eq!(timer.read(0xFF05), 0x10);
+        assert_eq!(interrupts.read_flag() & TIMER_INTERRUPT, 0);
+    }
+}